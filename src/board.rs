@@ -0,0 +1,140 @@
+use crate::game::*;
+use crate::snake::*;
+use crate::map::*;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
+
+/// Board topology
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Topology {
+    Bounded, // Snakes die on the border
+    Wrapping, // Snakes leaving on one side come back on the other side
+}
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bounded" => Ok(Topology::Bounded),
+            "wrapping" => Ok(Topology::Wrapping),
+            _ => Err(format!("Unknown topology {}", s)),
+        }
+    }
+}
+
+/// Smallest size of the safe zone, it stops shrinking there
+pub const MIN_ZONE_SIZE: usize = 4;
+
+/// Playable area, from min to max included
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub min: Point,
+    pub max: Point,
+}
+
+/// Board geometry
+/// Border cells are on x = 1, x = width, y = 1 and y = height,
+/// all coordinate arithmetic goes through this structure
+///
+/// The border can move inwards in a shrinking arena, `shrink` rings of cells are then out of the safe zone.
+/// Portals link pairs of cells, a step onto one end of a portal ends on the other end
+#[derive(Debug, Clone)]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    pub topology: Topology,
+    pub walls: HashSet<Point>,
+    pub shrink: usize, // Rings of cells closed around the border
+    pub portals: HashMap<Point, Point>, // Other end of each portal cell
+}
+impl Board {
+    /// Create new Board
+    pub fn new(width: usize, height: usize, topology: Topology) -> Self {
+        Board { width, height, topology, walls: HashSet::new(), shrink: 0, portals: HashMap::new() }
+    }
+
+    /// Create a Board from a map layout
+    pub fn from_map(map: &Map, topology: Topology) -> Self {
+        let mut board = Board::new(map.width, map.height, topology);
+        board.walls = map.walls.iter().cloned().collect();
+        for portal in map.portals.iter() {
+            board.portals.insert(portal.a.clone(), portal.b.clone());
+            board.portals.insert(portal.b.clone(), portal.a.clone());
+        }
+        return board;
+    }
+
+    /// Check if a point is a wall
+    pub fn is_wall(&self, p: &Point) -> bool {
+        return self.walls.contains(p);
+    }
+
+    /// Check if a point is one end of a portal
+    pub fn is_portal(&self, p: &Point) -> bool {
+        return self.portals.contains_key(p);
+    }
+
+    /// Check if a point is on or beyond the border, or out of the safe zone
+    pub fn is_border(&self, p: &Point) -> bool {
+        let zone = self.zone(self.shrink);
+        return p.x < zone.min.x || p.x > zone.max.x || p.y < zone.min.y || p.y > zone.max.y;
+    }
+
+    /// Safe zone once `shrink` rings are closed
+    pub fn zone(&self, shrink: usize) -> Zone {
+        let shrink = shrink as u16;
+        return Zone {
+            min: Point { x: 2 + shrink, y: 2 + shrink },
+            max: Point { x: self.width as u16 - 1 - shrink, y: self.height as u16 - 1 - shrink },
+        };
+    }
+
+    /// Check if one more ring can be closed without making the safe zone too small
+    pub fn can_shrink(&self) -> bool {
+        let inside = self.width.min(self.height).saturating_sub(2);
+        return inside >= MIN_ZONE_SIZE + 2 * (self.shrink + 1);
+    }
+
+    /// Check if a point is out of the playing field, the border is only deadly in bounded mode
+    pub fn is_outside(&self, p: &Point) -> bool {
+        return self.topology == Topology::Bounded && self.is_border(p);
+    }
+
+    /// Next point in a direction
+    /// In bounded mode, the point may be on the border. In wrapping mode, it is always inside.
+    /// Stepping onto a portal inside the safe zone leads to its other end
+    pub fn step(&self, p: &Point, direction: &Direction) -> Point {
+        let (dx, dy): (i32, i32) = match direction {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (1, -1),
+            Direction::DownLeft => (-1, 1),
+            Direction::DownRight => (1, 1),
+        };
+        let x = Board::shift(p.x, dx, self.width, &self.topology);
+        let y = Board::shift(p.y, dy, self.height, &self.topology);
+        let next = Point { x, y };
+        if !self.is_border(&next) {
+            if let Some(exit) = self.portals.get(&next) {
+                return exit.clone();
+            }
+        }
+        return next;
+    }
+
+    /// Shift a coordinate, inside cells go from 2 to size - 1
+    fn shift(c: u16, d: i32, size: usize, topology: &Topology) -> u16 {
+        let c = c as i32 + d;
+        match topology {
+            Topology::Bounded => c.clamp(0, u16::MAX as i32) as u16,
+            Topology::Wrapping => {
+                let inside = size as i32 - 2;
+                return ((c - 2).rem_euclid(inside) + 2) as u16;
+            },
+        }
+    }
+}
@@ -1,5 +1,11 @@
 use crate::snake::*;
 use crate::game::*;
+use crate::stats::*;
+use crate::food::*;
+use crate::board::*;
+use crate::map::*;
+use crate::mode::*;
+use crate::effect::*;
 
 use serde::{Serialize, Deserialize};
 use std::net::{TcpStream};
@@ -7,7 +13,7 @@ use std::io::{Write, BufRead, BufReader, BufWriter};
 
 
 // Information necessary for server-client connection
-pub const SERVER_ADDR: &'static str = "127.0.0.1";
+pub const SERVER_ADDR: &str = "127.0.0.1";
 pub const SERVER_PORT: usize = 8080;
 
 
@@ -24,20 +30,19 @@ pub struct Stream<'a> {
 /// Serialize object and send it as a json to the server
 pub fn send<T>(stream: &mut Stream, object: T) where T: Serialize {
     let payload = format!("{}\n", serde_json::to_string(&object).unwrap());
-    stream.writer.write(payload.as_bytes()).unwrap();
+    stream.writer.write_all(payload.as_bytes()).unwrap();
     stream.writer.flush().unwrap();
 }
 
 /// Wait for client message, read it and deserialize it depeding on T
 pub fn receive<'a, T>(stream: &mut Stream, response: &'a mut String) -> Result<T, ()> where T: Deserialize<'a> {
     let message = stream.reader.read_line(response);
-    let read_num;
 
     // Error handling
-    match message {
-        Ok(num) => read_num = num,
+    let read_num = match message {
+        Ok(num) => num,
         Err(_) => return Err(()),
-    }
+    };
 
     // If nothing coundn't be read, it means connection has ended
     if read_num == 0 {
@@ -53,23 +58,38 @@ pub fn receive<'a, T>(stream: &mut Stream, response: &'a mut String) -> Result<T
 /*----------------------------------------------------------------------*/
 
 /// Direction message
-/// Direction: Up, Down, Left, Right
+/// Direction: Up, Down, Left, Right, UpLeft, UpRight, DownLeft, DownRight
+/// Diagonal directions are only accepted when the config's `directions` is `Diagonal`
 #[derive(Deserialize)]
 pub struct DirectionMessage {
     pub direction: Direction,
 }
 
-/// Force start message
+/// Lobby message, to force the game to start and/or to ask to join a team
 #[derive(Deserialize)]
 pub struct ForceStartMessage {
+    #[serde(default)]
     pub force_start: bool,
+    #[serde(default)]
+    pub team: Option<usize>,
 }
 
 /// Turn data
 #[derive(Serialize, Clone)]
 pub struct TurnData {
     pub snakes: Vec<Vec<Point>>,
-    pub food: Point,
+    pub food: Vec<Food>,
+    pub legal_directions: Vec<Vec<Direction>>,
+    pub stats: Vec<PlayerStats>,
+    pub zone: Zone,
+    pub next_zone: Option<ZoneWarning>,
+    pub effects: Vec<Effects>,
+    pub speeds: Vec<usize>,
+    pub interval: usize,
+    pub hunger: Vec<Option<usize>>,
+    pub teams: Vec<usize>,
+    pub obstacles: Vec<Point>,
+    pub objective: Option<Objective>,
 }
 
 /// Turn data
@@ -79,14 +99,15 @@ pub struct StateData {
 }
 
 /// Game events
-/// GameEvent: Start, NewTurn
+/// GameEvent: Start, NewTurn, GameOver, and turn events such as Cut
 #[derive(Serialize)]
 pub struct EventMessage {
     pub event: GameEvent,
 }
 
 /// Game state message
-/// GameState: Ready, Playing, Lost
+/// GameState: Ready, Playing, Lost, Respawning { turns }
+/// `turns` is the number of turns before the snake is back on the field
 #[derive(Serialize, Clone)]
 pub struct StateMessage {
     pub state: GameState,
@@ -98,8 +119,19 @@ pub struct GameConfigMessage {
     pub id: usize,
     pub width: usize,
     pub height: usize,
+    pub mode: ModeKind,
+    pub topology: Topology,
+    pub map: Map,
     pub snakes: Vec<Vec<Point>>,
-    pub food: Point,
+    pub food: Vec<Food>,
+    pub speed: usize,
+    pub max_clients: usize,
+    pub seed: u64,
+    pub map_seed: Option<u64>, // Seed the map was generated from, it differs from seed when that one couldn't generate a map
+    pub teams: Vec<usize>, // Team of each player
+    pub directions: DirectionSet, // Directions available in this game
+    pub diagonal_crossing: bool, // Whether diagonal moves can cross between two cells of a snake
+    pub legal_directions: Vec<Direction>,
 }
 
 /// Turn message
@@ -107,5 +139,30 @@ pub struct GameConfigMessage {
 pub struct TurnMessage {
     pub id: usize,
     pub snakes: Vec<Vec<Point>>,
-    pub food: Point,
+    pub food: Vec<Food>,
+    pub legal_directions: Vec<Direction>,
+    pub stats: Vec<PlayerStats>,
+    pub zone: Zone, // Current safe zone
+    pub next_zone: Option<ZoneWarning>, // Safe zone after the arena shrinks next
+    pub effects: Vec<Effects>, // Power-up effects active on each snake, with their remaining turns
+    pub speeds: Vec<usize>, // Speed of each snake, in quarters of cell per tick
+    pub interval: usize, // Time until the next turn, in milliseconds
+    pub hunger: Vec<Option<usize>>, // Turns before each snake loses a segment to hunger
+    pub teams: Vec<usize>, // Team of each player
+    pub obstacles: Vec<Point>, // Walls added since the beginning of the game
+    pub objective: Option<Objective>, // Progress towards the shared objective, in cooperative mode
+}
+
+/// Standings message
+/// Final ranking of players, sent when the game is over
+#[derive(Serialize)]
+pub struct StandingsMessage {
+    pub standings: Vec<Standing>,
+}
+
+/// Error message
+/// Sent when a client message is rejected
+#[derive(Serialize)]
+pub struct ErrorMessage {
+    pub error: String,
 }
\ No newline at end of file
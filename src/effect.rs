@@ -0,0 +1,63 @@
+use serde::{Serialize, Deserialize};
+
+// Turns a speed boost lasts
+pub const SPEED_BOOST_TURNS: usize = 5;
+// Turns a ghost effect lasts
+pub const GHOST_TURNS: usize = 5;
+// Turns a shield lasts if no collision uses it
+pub const SHIELD_TURNS: usize = 20;
+
+/// Effects given by power-ups
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum EffectKind {
+    SpeedBoost, // Doubles the speed
+    Ghost, // Passes through other snakes, and other snakes pass through it
+    Shield, // Survives one collision, staying where it was
+}
+impl EffectKind {
+    /// Number of turns the effect lasts
+    pub fn duration(&self) -> usize {
+        match self {
+            EffectKind::SpeedBoost => SPEED_BOOST_TURNS,
+            EffectKind::Ghost => GHOST_TURNS,
+            EffectKind::Shield => SHIELD_TURNS,
+        }
+    }
+}
+
+/// An effect active on a snake
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub turns: usize, // Remaining turns, including the current one
+}
+
+/// Effects active on a snake
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Effects(pub Vec<Effect>);
+impl Effects {
+    /// Check if an effect is active
+    pub fn has(&self, kind: &EffectKind) -> bool {
+        return self.0.iter().any(|e| e.kind == *kind);
+    }
+
+    /// Start an effect, or restart it if it is already active
+    pub fn add(&mut self, kind: EffectKind) {
+        self.remove(&kind);
+        let turns = kind.duration();
+        self.0.push(Effect { kind, turns });
+    }
+
+    /// Stop an effect
+    pub fn remove(&mut self, kind: &EffectKind) {
+        self.0.retain(|e| e.kind != *kind);
+    }
+
+    /// Count down at the end of a turn, expired effects are removed
+    pub fn tick(&mut self) {
+        for effect in self.0.iter_mut() {
+            effect.turns -= 1;
+        }
+        self.0.retain(|e| e.turns > 0);
+    }
+}
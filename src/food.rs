@@ -0,0 +1,98 @@
+use crate::game::*;
+use crate::stats::*;
+use crate::effect::*;
+use serde::{Serialize, Deserialize};
+
+/// Food kinds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FoodKind {
+    Normal, // Grows the snake by one
+    Bonus, // Grows the snake by one, worth more points
+    Shrink, // Shrinks the snake by one
+    Poison, // Kills the snake
+    SpeedBoost, // Power-up, the snake moves twice per turn for a while
+    Ghost, // Power-up, the snake passes through other snakes for a while
+    Shield, // Power-up, the snake survives its next collision
+    Corpse, // Left by eliminated snakes, grows the snake by one without giving points
+}
+impl FoodKind {
+    /// Points given when eaten
+    pub fn points(&self) -> usize {
+        match self {
+            FoodKind::Normal => FOOD_POINTS,
+            FoodKind::Bonus => BONUS_POINTS,
+            _ => 0,
+        }
+    }
+
+    /// Effect given when picked up, for power-ups
+    pub fn effect(&self) -> Option<EffectKind> {
+        match self {
+            FoodKind::SpeedBoost => Some(EffectKind::SpeedBoost),
+            FoodKind::Ghost => Some(EffectKind::Ghost),
+            FoodKind::Shield => Some(EffectKind::Shield),
+            _ => None,
+        }
+    }
+
+    /// Check if the snake keeps its tail when eating it
+    pub fn grows(&self) -> bool {
+        return matches!(self, FoodKind::Normal | FoodKind::Bonus | FoodKind::Corpse);
+    }
+}
+
+/// A food item
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Food {
+    pub position: Point,
+    pub kind: FoodKind,
+    pub decay: Option<usize>, // Turns left before the item disappears
+}
+
+/// Relative weights used to pick the kind of a new food item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FoodWeights {
+    pub normal: u32,
+    pub bonus: u32,
+    pub shrink: u32,
+    pub poison: u32,
+    pub speed_boost: u32,
+    pub ghost: u32,
+    pub shield: u32,
+}
+impl Default for FoodWeights {
+    fn default() -> Self {
+        FoodWeights { normal: 1, bonus: 0, shrink: 0, poison: 0, speed_boost: 0, ghost: 0, shield: 0 }
+    }
+}
+impl FoodWeights {
+    /// Kinds paired with their weight
+    pub fn kinds(&self) -> Vec<(FoodKind, u32)> {
+        return vec![
+            (FoodKind::Normal, self.normal),
+            (FoodKind::Bonus, self.bonus),
+            (FoodKind::Shrink, self.shrink),
+            (FoodKind::Poison, self.poison),
+            (FoodKind::SpeedBoost, self.speed_boost),
+            (FoodKind::Ghost, self.ghost),
+            (FoodKind::Shield, self.shield),
+        ];
+    }
+
+    /// Sum of all weights
+    pub fn total(&self) -> u32 {
+        return self.kinds().iter().map(|(_, weight)| weight).sum();
+    }
+
+    /// Pick the kind matching a value in 0..total()
+    pub fn pick(&self, mut value: u32) -> FoodKind {
+        for (kind, weight) in self.kinds() {
+            if value < weight {
+                return kind;
+            }
+            value -= weight;
+        }
+        return FoodKind::Normal;
+    }
+}
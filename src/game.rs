@@ -1,144 +1,809 @@
 use crate::snake::*;
-use rand::Rng;
+use crate::settings::*;
+use crate::stats::*;
+use crate::food::*;
+use crate::board::*;
+use crate::map::*;
+use crate::grid::*;
+use crate::spawn::*;
+use crate::mode::*;
+use crate::effect::*;
+use crate::schedule::*;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use serde::{Serialize, Deserialize};
-
-pub const SPEED: usize = 1000;
-
-const WIDTH: usize = 20;
-const HEIGHT: usize = 20;
+use std::cmp::Reverse;
 
 /// A point
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: u16,
     pub y: u16,
 }
 
 /// A Game Event
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub enum GameEvent {
     WaitInLobby,
     Start,
     NewTurn,
+    GameOver { winners: Vec<usize> },
+    Cut { attacker: usize, victim: usize, position: Point, segments: usize }, // Snake `victim` lost `segments` segments
 }
 
 /// Collision kinds
-enum Collision {
+#[derive(Debug, Clone, PartialEq)]
+pub enum Collision {
     None,
     Food,
-    BorderOrSnake,
+    Border,
+    Wall,
+    Snake(usize), // Head ran into the body of snake `id` (possibly its own)
+    Cut(usize), // Head ran into the body of snake `id`, which is cut there
+    HeadOn(usize), // Head reached the same cell as the head of snake `id`
 }
 
 /// Game state
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub enum GameState {
     Ready,
     Playing,
+    Respawning { turns: usize }, // Eliminated, back on the field in `turns` turns
     Lost,
 }
 
 pub struct Game {
     pub snakes: Vec<Snake>,
-    pub food: Point,
-    pub width: usize,
-    pub height: usize,
+    pub food: Vec<Food>,
+    pub board: Board,
+    pub map: Map,
+    pub grid: Grid,
     pub states: Vec<GameState>,
+    pub stats: Vec<PlayerStats>,
+    pub effects: Vec<Effects>, // Power-up effects active on each snake
+    hunger: Vec<usize>, // Turns since each snake last ate, the current one included
+    corpses: Vec<Point>, // Cells of eliminated snakes waiting to turn into food
+    progress: Vec<usize>, // Distance covered by each snake towards its next cell, out of MAX_SPEED
+    pub settings: GameSettings,
+    pub seed: u64,
+    pub map_seed: Option<u64>, // Seed the map was generated from, if it was generated
+    pub map_error: Option<String>, // Why the game seed couldn't generate a map, when another seed was used
+    pub schedule: TickSchedule, // Time between turns
+    pub teams: Vec<usize>, // Team of each player, players are on their own in a free-for-all
+    pub starting_teams: usize,
+    pub turn: usize, // Turns played so far
+    pub obstacles: Vec<Point>, // Walls added during the game
+    pub events: Vec<GameEvent>, // Events of the last turn
+    mode: Box<dyn GameMode>,
+    rng: StdRng,
 }
 impl Game {
     /// Create new Game
-    pub fn new(nb: usize) -> Self {
+    pub fn new(nb: usize, settings: &GameSettings) -> Result<Self, String> {
+        let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        // A random seed may not give a usable generated map, the map checked with the settings is taken then
+        let mut map_seed = settings.generator.as_ref().map(|_| seed);
+        let mut map_error = None;
+        let map = match settings.load_map(seed) {
+            Ok(map) => map,
+            Err(e) if settings.generator.is_some() => {
+                let fallback = settings.seed.unwrap_or(0);
+                map_seed = Some(fallback);
+                map_error = Some(e);
+                settings.load_map(fallback)?
+            },
+            Err(e) => return Err(e),
+        };
+        let board = Board::from_map(&map, settings.topology.clone());
         let mut snakes: Vec<Snake> = vec![];
         let mut states: Vec<GameState> = vec![];
+        let mut stats: Vec<PlayerStats> = vec![];
+        let spawns = match map.spawns.len() {
+            0 => SpawnPlanner::plan(&board, nb, settings.spawn_clearance, &settings.spawn_layout)?,
+            n if n < nb => return Err(format!("Map has {} spawn points for {} players", n, nb)),
+            _ => map.spawns.clone(),
+        };
         for id in 0..nb {
-            snakes.push(Snake::init(id, nb, WIDTH, HEIGHT));
+            let snake = Snake::spawn(spawns[id].position.clone(), spawns[id].direction.clone(), &board);
+            if snake.body.iter().any(|p| board.is_border(p) || board.is_wall(p) || board.is_portal(p)) {
+                return Err(format!("Snake {} doesn't fit on the map", id));
+            }
+            stats.push(PlayerStats::new(snake.body.len()));
+            snakes.push(snake);
             states.push(GameState::Ready);
         }
         let mut game = Game {
             snakes,
-            food: Point { x: 0, y: 0 }, // Food is initialized afterwards
-            width: WIDTH,
-            height: HEIGHT,
+            food: vec![], // Food is initialized afterwards
+            grid: Grid::new(&board),
+            board,
+            map,
             states,
+            stats,
+            effects: vec![Effects::default(); nb],
+            hunger: vec![0; nb],
+            corpses: vec![],
+            progress: vec![0; nb],
+            settings: settings.clone(),
+            seed,
+            map_seed,
+            map_error,
+            schedule: TickSchedule::new(settings),
+            teams: (0..nb).collect(),
+            starting_teams: nb,
+            turn: 0,
+            obstacles: vec![],
+            events: vec![],
+            mode: settings.mode.create(),
+            rng: StdRng::seed_from_u64(seed),
         };
-        game.create_food();
-        return game;
+        game.rebuild_grid();
+        for _ in 0..game.settings.max_food {
+            game.create_food();
+        }
+        return Ok(game);
     }
 
-    /// Check if a point overlaps with snakes
-    fn do_overlap(&self, point: Point) -> bool {
-        for snake in self.snakes.iter() {
-            if snake._do_overlap(point.clone()) {
-                return true;
+    /// Fill the occupancy grid from scratch
+    fn rebuild_grid(&mut self) {
+        self.grid = Grid::new(&self.board);
+        for id in 0..self.snakes.len() {
+            for p in self.snakes[id].body.clone() {
+                self.occupy(&p, id);
             }
         }
-        return false;
+        for food in self.food.iter() {
+            self.grid.set(&food.position, Cell::Food);
+        }
+    }
+
+    /// Check if a snake is a ghost
+    fn is_ghost(&self, id: usize) -> bool {
+        return self.effects[id].has(&EffectKind::Ghost);
+    }
+
+    /// Mark a cell as part of a snake
+    /// When a ghost overlaps another snake, the cell stays marked as the other snake's,
+    /// so that it keeps blocking snakes that are not ghosts
+    fn occupy(&mut self, p: &Point, id: usize) {
+        self.grid.add_segment(p);
+        if let Cell::Snake(other) = self.grid.get(p) {
+            if *other != id && self.is_ghost(id) {
+                return;
+            }
+        }
+        self.grid.set(p, Cell::Snake(id));
+    }
+
+    /// Free a cell left by a snake, or give it to another snake still there after a ghost went through
+    /// Bodies are only searched when snakes overlapped on the cell
+    fn release(&mut self, p: &Point, id: usize) {
+        let left = self.grid.remove_segment(p);
+        if *self.grid.get(p) != Cell::Snake(id) {
+            return;
+        }
+        self.grid.set(p, Cell::Empty);
+        if left == 0 {
+            return;
+        }
+        if let Some(other) = (0..self.snakes.len()).find(|&other| other != id && self.snakes[other].body.contains(p)) {
+            self.grid.set(p, Cell::Snake(other));
+        }
     }
 
-    /// Add a food item on the field, don't overlap with snakes
+    /// Change a snake's body and keep the grid up to date
+    /// The change must add at most one head, and remove segments from the tail
+    fn update_snake<F: FnOnce(&mut Snake, &Board)>(&mut self, id: usize, change: F) {
+        let snake = &mut self.snakes[id];
+        let old_len = snake.body.len();
+        let old_tail: Vec<Point> = snake.body.iter().take(2).cloned().collect();
+        change(snake, &self.board);
+
+        let removed = old_len + 1 - snake.body.len();
+        let head = snake.body.last().unwrap().clone();
+        for p in old_tail.iter().take(removed) {
+            self.release(p, id);
+        }
+        self.occupy(&head, id);
+    }
+
+    /// Index of the food item at a point, if any
+    fn food_at(&self, point: &Point) -> Option<usize> {
+        return self.food.iter().position(|f| f.position == *point);
+    }
+
+    /// Random free cell where food can spawn, in a food zone if the map has some
+    fn random_food_point(&mut self) -> Option<Point> {
+        if self.map.food_zones.is_empty() {
+            return self.grid.random_free(&mut self.rng);
+        }
+        let free: Vec<&Point> = self.map.food_zones.iter().filter(|p| self.grid.is_free(p)).collect();
+        if free.is_empty() {
+            return None;
+        }
+        return Some(free[self.rng.gen_range(0..free.len())].clone());
+    }
+
+    /// Add a food item on a free cell, if there is one left
     fn create_food(&mut self) {
-        let mut rng = rand::thread_rng();
-        let mut point = Point {
-            x: rng.gen_range(2..self.width-1) as u16,
-            y: rng.gen_range(2..self.height-1) as u16
+        let point = match self.random_food_point() {
+            Some(point) => point,
+            None => return,
         };
-        while self.do_overlap(point.clone()) {
-            point = Point {
-                x: rng.gen_range(2..self.width-1) as u16,
-                y: rng.gen_range(2..self.height-1) as u16,
-            };
+        let weights = &self.settings.food_weights;
+        let kind = weights.pick(self.rng.gen_range(0..weights.total()));
+        self.grid.set(&point, Cell::Food);
+        self.food.push(Food { position: point, kind, decay: None });
+    }
+
+    /// Maybe spawn a new food item, as long as there are less than `max_food` on the field
+    /// Food left by corpses doesn't count
+    fn spawn_food(&mut self) {
+        let spawned = self.food.iter().filter(|f| f.kind != FoodKind::Corpse).count();
+        if spawned < self.settings.max_food && self.rng.gen_bool(self.settings.food_spawn_chance) {
+            self.create_food();
+        }
+    }
+
+    /// Turn the cells left by eliminated snakes into food, where they are still free
+    fn drop_corpses(&mut self) {
+        for position in std::mem::take(&mut self.corpses) {
+            if self.grid.is_free(&position) {
+                self.grid.set(&position, Cell::Food);
+                self.food.push(Food { position, kind: FoodKind::Corpse, decay: Some(self.settings.corpse_decay) });
+            }
+        }
+    }
+
+    /// Count down food items that decay, and remove those that expired
+    fn decay_food(&mut self) {
+        let (expired, kept): (Vec<Food>, Vec<Food>) = std::mem::take(&mut self.food)
+            .into_iter()
+            .partition(|f| f.decay == Some(0));
+        self.food = kept;
+        for food in expired {
+            self.grid.clear(&food.position, &Cell::Food);
+        }
+        for decay in self.food.iter_mut().filter_map(|f| f.decay.as_mut()) {
+            *decay -= 1;
+        }
+    }
+
+    /// Check if a snake is still moving on the field
+    fn is_alive(&self, id: usize) -> bool {
+        return matches!(self.states[id], GameState::Ready | GameState::Playing);
+    }
+
+    /// Ids of snakes still moving on the field
+    pub fn alive_ids(&self) -> Vec<usize> {
+        return (0..self.snakes.len()).filter(|&id| self.is_alive(id)).collect();
+    }
+
+    /// Put players in teams, as they requested in the lobby when possible,
+    /// other players join the smallest team
+    pub fn assign_teams(&mut self, requests: &[Option<usize>]) {
+        let nb = self.settings.teams;
+        if nb == 0 {
+            return;
+        }
+        let mut sizes = vec![0; nb];
+        let mut teams: Vec<Option<usize>> = vec![None; self.snakes.len()];
+        for (id, request) in requests.iter().enumerate().take(teams.len()) {
+            if let Some(team) = request.filter(|&team| team < nb) {
+                teams[id] = Some(team);
+                sizes[team] += 1;
+            }
+        }
+        for team in teams.iter_mut().filter(|t| t.is_none()) {
+            let smallest = (0..nb).min_by_key(|&t| sizes[t]).unwrap();
+            *team = Some(smallest);
+            sizes[smallest] += 1;
+        }
+        self.teams = teams.into_iter().flatten().collect();
+        self.starting_teams = self.teams_of(&(0..self.snakes.len()).collect::<Vec<usize>>()).len();
+    }
+
+    /// Teams of some players, without duplicates
+    pub fn teams_of(&self, ids: &[usize]) -> Vec<usize> {
+        let mut teams: Vec<usize> = ids.iter().map(|&id| self.teams[id]).collect();
+        teams.sort_unstable();
+        teams.dedup();
+        return teams;
+    }
+
+    /// Players of some teams, whether they are still in the game or not
+    pub fn members_of(&self, teams: &[usize]) -> Vec<usize> {
+        return (0..self.snakes.len()).filter(|&id| teams.contains(&self.teams[id])).collect();
+    }
+
+    /// Score of a team, the sum of its players' scores
+    pub fn team_score(&self, team: usize) -> usize {
+        return self.members_of(&[team]).iter().map(|&id| self.stats[id].score).sum();
+    }
+
+    /// Ids of players still in the game, moving on the field or waiting to respawn
+    pub fn in_game_ids(&self) -> Vec<usize> {
+        return (0..self.snakes.len()).filter(|&id| self.states[id] != GameState::Lost).collect();
+    }
+
+    /// Mode of the game, announced to clients
+    pub fn mode(&self) -> ModeKind {
+        return self.mode.kind();
+    }
+
+    /// Run a mode hook, the mode can change the game while it runs
+    fn with_mode<R, F: FnOnce(&mut dyn GameMode, &mut Game) -> R>(&mut self, hook: F) -> R {
+        let mut mode = std::mem::replace(&mut self.mode, Box::new(Classic));
+        let result = hook(mode.as_mut(), self);
+        self.mode = mode;
+        return result;
+    }
+
+    /// Winners, once the game is over according to its mode
+    pub fn winners(&self) -> Option<Vec<usize>> {
+        return self.mode.winners(self);
+    }
+
+    /// Check if the game is over
+    pub fn is_over(&self) -> bool {
+        return self.winners().is_some();
+    }
+
+    /// Close one more ring of the arena
+    /// Snakes with a segment out of the new safe zone are eliminated, food there is removed
+    pub fn shrink_zone(&mut self) {
+        self.board.shrink += 1;
+        for id in self.alive_ids() {
+            if self.snakes[id].body.iter().any(|p| self.board.is_border(p)) {
+                self.eliminate(id, DeathCause::Border);
+            }
+        }
+        let board = &self.board;
+        self.food.retain(|f| !board.is_border(&f.position));
+        self.rebuild_grid();
+        self.drop_corpses();
+    }
+
+    /// Add a wall on a random free cell, away from snake heads
+    pub fn spawn_obstacle(&mut self) -> Option<Point> {
+        // Give up after a few tries on crowded boards
+        for _ in 0..10 {
+            let p = self.grid.random_free(&mut self.rng)?;
+            let near_head = self.alive_ids().iter()
+                .any(|&id| distance(self.snakes[id].body.last().unwrap(), &p) <= 2);
+            if near_head {
+                continue;
+            }
+            self.board.walls.insert(p.clone());
+            self.grid.set(&p, Cell::Wall);
+            self.obstacles.push(p.clone());
+            return Some(p);
+        }
+        return None;
+    }
+
+    /// Progress towards the shared objective, in cooperative modes
+    pub fn objective(&self) -> Option<Objective> {
+        return self.mode.objective(self);
+    }
+
+    /// Next safe zone, if the mode makes the arena shrink
+    pub fn upcoming_zone(&self) -> Option<ZoneWarning> {
+        return self.mode.upcoming_zone(self);
+    }
+
+    /// Remove a player from the game, with its snake, state and stats
+    pub fn remove_player(&mut self, id: usize) {
+        self.snakes.remove(id);
+        self.states.remove(id);
+        self.stats.remove(id);
+        self.effects.remove(id);
+        self.progress.remove(id);
+        self.hunger.remove(id);
+        self.teams.remove(id);
+        self.rebuild_grid();
+    }
+
+    /// Take an eliminated snake off the board, unless it is out of the game and its corpse is kept as an obstacle
+    /// With `corpse_food`, its cells turn into food at the end of the step.
+    /// A snake with lives left respawns after `respawn_delay` turns
+    fn eliminate(&mut self, id: usize, cause: DeathCause) {
+        let respawns = self.respawns(id);
+        self.stats[id].deaths += 1;
+        self.stats[id].cause_of_death = Some(cause);
+        self.effects[id] = Effects::default();
+        self.states[id] = match respawns {
+            true => GameState::Respawning { turns: self.settings.respawn_delay },
+            false => GameState::Lost,
+        };
+        if self.settings.corpse_food {
+            self.corpses.extend(self.snakes[id].body.iter().cloned());
+        }
+        if !self.settings.keep_corpses || respawns {
+            for p in std::mem::take(&mut self.snakes[id].body) {
+                self.release(&p, id);
+            }
         }
-        self.food = point;
     }
 
-    /// Check collisions between snakes
-    fn check_snake_collisions(&self, snake: &Snake) -> bool {
-        let last = snake.body.last().unwrap();
-        for s in self.snakes.iter() {
-            for p in s.body.iter() {
-                if (p as *const _) != (last as *const _) && p.x == last.x && p.y == last.y {
-                    return true;
+    /// Resolve the moves of all moving snakes at once
+    /// Returns the collision of each snake, and whether it is fatal
+    ///
+    /// Moves are checked once, then snakes staying in place are looked for.
+    /// Snakes surviving a collision stay, thanks to the mode or a shield.
+    /// Snakes dying with their corpse kept stay too, after a collision or poison.
+    /// Their tails don't move, so moves are checked again until no more snakes stay.
+    /// The mode decides once per snake if its collision is fatal.
+    /// A shield is used up instead, if the snake has one
+    fn resolve_moves(&mut self, moving: &[bool]) -> (Vec<Collision>, Vec<bool>) {
+        let n = self.snakes.len();
+        let mut staying = vec![false; n];
+        let mut decided: Vec<Option<(Collision, bool)>> = vec![None; n];
+        loop {
+            let collisions = self.check_moves(moving, &staying);
+            let mut changed = false;
+            for id in 0..n {
+                if decided[id].is_some() || !moving[id] || !self.is_alive(id) {
+                    continue;
+                }
+                let collision = &collisions[id];
+                let fatal = match collision {
+                    Collision::None | Collision::Cut(_) => continue,
+                    Collision::Food if !self.eats_poison(id) => continue,
+                    Collision::Food => false,
+                    _ => self.with_mode(|mode, game| mode.on_collision(game, id, collision)),
+                };
+                let shielded = fatal && self.effects[id].has(&EffectKind::Shield);
+                if shielded {
+                    self.effects[id].remove(&EffectKind::Shield);
+                }
+                let fatal = fatal && !shielded;
+                let dies = fatal || *collision == Collision::Food;
+                let survives = *collision != Collision::Food && !fatal;
+                staying[id] = survives || (dies && self.leaves_corpse(id));
+                changed |= staying[id];
+                decided[id] = Some((collision.clone(), fatal));
+            }
+            if !changed {
+                return (0..n)
+                    .map(|id| decided[id].take().unwrap_or((collisions[id].clone(), false)))
+                    .unzip();
+            }
+        }
+    }
+
+    /// Check if a snake would move onto poison
+    fn eats_poison(&self, id: usize) -> bool {
+        let head = self.snakes[id].next_head(&self.board);
+        return matches!(self.food_at(&head), Some(i) if self.food[i].kind == FoodKind::Poison);
+    }
+
+    /// Check if a snake eliminated now would have lives left
+    fn respawns(&self, id: usize) -> bool {
+        return self.settings.lives == 0 || self.stats[id].deaths + 1 < self.settings.lives;
+    }
+
+    /// Check if a snake eliminated now would stay on the board as an obstacle
+    fn leaves_corpse(&self, id: usize) -> bool {
+        return self.settings.keep_corpses && !self.respawns(id);
+    }
+
+    /// Check the moves of all moving snakes at once, without modifying the game
+    ///
+    /// Every moving snake moves one cell, then all new heads are checked against the same board:
+    /// - a head on the border dies
+    /// - a head on a body dies, bodies are taken after the move, so a tail that moves away
+    ///   frees its cell, unless its snake is eating or `staying` (two snakes swapping cells hit each other's neck)
+    /// - with `tail_cutting`, a head on the body of another snake cuts it there instead
+    /// - heads on the same cell all die, or only the strictly longest survives if
+    ///   `length_tiebreak` is set. This also decides who gets food reached by several heads
+    /// - a surviving head on a food item eats it
+    /// - without `diagonal_crossing`, a diagonal move between two consecutive cells of a snake hits it,
+    ///   so does a diagonal move crossing another one
+    ///
+    /// Ghosts ignore other snakes, and other snakes ignore them. Without `friendly_fire`, teammates ignore each other
+    fn check_moves(&self, moving: &[bool], staying: &[bool]) -> Vec<Collision> {
+        let n = self.snakes.len();
+
+        // New heads, lost and waiting snakes stay where they are
+        let heads: Vec<Option<Point>> = (0..n)
+            .map(|id| if moving[id] && self.is_alive(id) { Some(self.snakes[id].next_head(&self.board)) } else { None })
+            .collect();
+        let passes = |id: usize, other: usize| id != other && (self.is_ghost(id) || self.is_ghost(other)
+            || (!self.settings.friendly_fire && self.teams[id] == self.teams[other]));
+        // Tails stay in place when eating, or when the snake stays
+        let moving_tail: Vec<bool> = (0..n)
+            .map(|id| match &heads[id] {
+                Some(_) if staying[id] => false,
+                Some(head) => !matches!(self.food_at(head), Some(i) if self.food[i].kind.grows()),
+                None => false,
+            })
+            .collect();
+
+        let mut collisions = vec![Collision::None; n];
+        for id in 0..n {
+            let head = match &heads[id] {
+                Some(head) => head,
+                None => continue,
+            };
+
+            if self.board.is_outside(head) {
+                collisions[id] = Collision::Border;
+                continue;
+            }
+
+            if !self.settings.diagonal_crossing {
+                let crossed = (0..n)
+                    .find(|&other| !passes(id, other) && self.crosses(id, other, &heads[other], moving_tail[other]));
+                if let Some(other) = crossed {
+                    collisions[id] = Collision::Snake(other);
+                    continue;
                 }
             }
+
+            match self.grid.get(head) {
+                Cell::Wall => {
+                    collisions[id] = Collision::Wall;
+                    continue;
+                },
+                Cell::Snake(other) if passes(id, *other) => (),
+                Cell::Snake(other) if !(moving_tail[*other] && *head == self.snakes[*other].body[0]) => {
+                    if !self.settings.tail_cutting || *other == id || !self.is_alive(*other) {
+                        collisions[id] = Collision::Snake(*other);
+                        continue;
+                    }
+                    collisions[id] = Collision::Cut(*other);
+                },
+                _ => (),
+            }
+
+            // Longest opponent reaching the same cell, lowest id first
+            let opponent = (0..n)
+                .filter(|&other| other != id && !passes(id, other) && heads[other].as_ref() == Some(head))
+                .max_by_key(|&other| (self.snakes[other].body.len(), Reverse(other)));
+            if let Some(other) = opponent {
+                let longer = self.snakes[id].body.len() > self.snakes[other].body.len();
+                if !(self.settings.length_tiebreak && longer) {
+                    collisions[id] = Collision::HeadOn(other);
+                    continue;
+                }
+            }
+
+            if collisions[id] == Collision::None && *self.grid.get(head) == Cell::Food {
+                collisions[id] = Collision::Food;
+            }
+        }
+        return collisions;
+    }
+
+    /// Check if the diagonal move of snake `id` passes between two consecutive cells of snake `other`,
+    /// its moving tail excepted, or crosses the move of its head to `other_head`
+    fn crosses(&self, id: usize, other: usize, other_head: &Option<Point>, moving_tail: bool) -> bool {
+        let (vertical, horizontal) = match self.snakes[id].direction.components() {
+            Some(components) => components,
+            None => return false,
+        };
+        let head = self.snakes[id].body.last().unwrap();
+        let corners = (self.board.step(head, &vertical), self.board.step(head, &horizontal));
+        let is_corners = |a: &Point, b: &Point| (*a == corners.0 && *b == corners.1) || (*a == corners.1 && *b == corners.0);
+
+        let body = &self.snakes[other].body;
+        let segment = body.windows(2).enumerate()
+            .any(|(i, cells)| !(i == 0 && moving_tail) && is_corners(&cells[0], &cells[1]));
+        let head_move = match (body.last(), other_head) {
+            (Some(from), Some(to)) => is_corners(from, to),
+            _ => false,
+        };
+        return segment || head_move;
+    }
+
+    /// Make a snake eat the food item in front of it
+    fn eat(&mut self, id: usize) {
+        let head = self.snakes[id].next_head(&self.board);
+        let i = self.food_at(&head).unwrap();
+        let food = self.food.remove(i);
+        match food.kind {
+            FoodKind::Normal | FoodKind::Bonus | FoodKind::Corpse => self.update_snake(id, |s, _| s._grow(head)),
+            FoodKind::Shrink => self.update_snake(id, |s, board| {
+                s._move(board);
+                s._shrink();
+            }),
+            FoodKind::Poison => {
+                self.grid.clear(&food.position, &Cell::Food);
+                self.eliminate(id, DeathCause::Poison);
+            },
+            FoodKind::SpeedBoost | FoodKind::Ghost | FoodKind::Shield => {
+                self.update_snake(id, |s, board| s._move(board));
+                self.effects[id].add(food.kind.effect().unwrap());
+            },
+        }
+        if self.is_alive(id) && food.kind.effect().is_none() {
+            self.hunger[id] = 0;
+            self.stats[id].eat(food.kind.points());
+            self.schedule.on_food();
         }
-        return false;
+        self.with_mode(|mode, game| mode.on_food_eaten(game, id, &food));
     }
 
-    /// Check all kinds of collisions
-    fn check_collisions(&self, snake: &Snake) -> Collision {
-        if snake._check_border_collisions(self.width, self.height) ||
-            self.check_snake_collisions(snake) {
-                return Collision::BorderOrSnake;
+    /// Move some snakes by one cell
+    fn play_step(&mut self, moving: &[bool]) {
+        let (collisions, fatal) = self.resolve_moves(moving);
+        // Snakes eliminated before or during this step are corpses, running into them gives no kill
+        let corpse: Vec<bool> = (0..self.snakes.len()).map(|id| !self.is_alive(id) || fatal[id]).collect();
+        let mut cuts: Vec<(usize, usize)> = vec![];
+
+        for id in 0..self.snakes.len() {
+            if !moving[id] || !self.is_alive(id) {
+                continue;
+            }
+            let collision = &collisions[id];
+            // Corpses, and snakes surviving a collision, stay where they were before the collision
+            match *collision {
+                Collision::None => self.update_snake(id, |s, board| s._move(board)),
+                Collision::Food => self.eat(id),
+                Collision::Cut(other) => {
+                    self.update_snake(id, |s, board| s._move(board));
+                    cuts.push((id, other));
+                },
+                _ if !fatal[id] => (),
+                Collision::Border => self.eliminate(id, DeathCause::Border),
+                Collision::Wall => self.eliminate(id, DeathCause::Wall),
+                Collision::Snake(other) if other == id => self.eliminate(id, DeathCause::SelfCollision),
+                Collision::Snake(other) if corpse[other] => self.eliminate(id, DeathCause::Corpse),
+                Collision::Snake(other) => {
+                    self.stats[other].kill();
+                    self.eliminate(id, DeathCause::Snake { killer: other });
+                },
+                Collision::HeadOn(other) => {
+                    self.stats[other].kill();
+                    self.eliminate(id, DeathCause::HeadOn { killer: other });
+                },
             }
-        if snake._check_food_collision(self.food.clone()) {
-            return Collision::Food;
         }
-        return Collision::None;
+        // Bodies are cut once every snake has moved
+        for (attacker, victim) in cuts {
+            self.cut(attacker, victim);
+        }
+        self.drop_corpses();
     }
 
-    /// Move all snakes
-    fn move_snakes(&mut self) {
-        for snake in self.snakes.iter_mut() {
-            snake._move();
+    /// Cut a snake where the head of another one ran into it, the part behind the impact is removed.
+    /// A snake left shorter than `MIN_LENGTH` is eliminated
+    fn cut(&mut self, attacker: usize, victim: usize) {
+        let position = self.snakes[attacker].body.last().unwrap().clone();
+        let segments = match self.snakes[victim].body.iter().position(|p| *p == position) {
+            Some(i) if self.is_alive(victim) => i + 1,
+            _ => return,
+        };
+        if self.snakes[victim].body.len() - segments < MIN_LENGTH {
+            self.stats[attacker].kill();
+            self.eliminate(victim, DeathCause::Snake { killer: attacker });
+            return;
         }
+        for p in self.snakes[victim].body.drain(..segments).collect::<Vec<Point>>() {
+            self.release(&p, victim);
+        }
+        self.stats[attacker].cut(segments);
+        self.events.push(GameEvent::Cut { attacker, victim, position, segments });
+    }
+
+    /// Make snakes lose a segment every `hunger` turns without eating
+    /// A snake already at `MIN_LENGTH` starves to death
+    fn starve(&mut self) {
+        if self.settings.hunger == 0 {
+            return;
+        }
+        for id in self.alive_ids() {
+            if self.hunger[id] == 0 || !self.hunger[id].is_multiple_of(self.settings.hunger) {
+                continue;
+            }
+            if self.snakes[id].body.len() <= MIN_LENGTH {
+                self.eliminate(id, DeathCause::Starvation);
+                continue;
+            }
+            let tail = self.snakes[id].body[0].clone();
+            self.snakes[id]._shrink();
+            self.release(&tail, id);
+        }
+        self.drop_corpses();
+    }
+
+    /// Turns before each snake loses a segment to hunger, if hunger is enabled and the snake is alive
+    pub fn hunger(&self) -> Vec<Option<usize>> {
+        return (0..self.snakes.len())
+            .map(|id| match self.settings.hunger {
+                0 => None,
+                _ if !self.is_alive(id) => None,
+                every => Some(every - self.hunger[id] % every),
+            })
+            .collect();
+    }
+
+    /// Put a snake back on the field, on a safe spawn point
+    /// The snake keeps waiting if there is none
+    fn respawn(&mut self, id: usize) {
+        let mut planner = SpawnPlanner::new(&self.board, self.settings.spawn_clearance);
+        for snake in self.snakes.iter().filter(|s| !s.body.is_empty()) {
+            planner.add_body(snake.body.clone());
+        }
+        for food in self.food.iter() {
+            planner.block(food.position.clone());
+        }
+        let spawn = match planner.place(&self.map.spawns) {
+            Some(spawn) => spawn,
+            None => return,
+        };
+        self.snakes[id] = Snake::spawn(spawn.position, spawn.direction, &self.board);
+        for p in self.snakes[id].body.clone() {
+            self.occupy(&p, id);
+        }
+        self.states[id] = GameState::Playing;
+        self.stats[id].cause_of_death = None;
+        self.stats[id].length = self.snakes[id].body.len();
+        self.progress[id] = 0;
+        self.hunger[id] = 0;
+    }
+
+    /// Speed of a snake, in quarters of cell per tick
+    /// Snakes slow down as they grow if `slow_down_every` is set, speed boosts double the speed
+    pub fn speed(&self, id: usize) -> usize {
+        let mut speed = NORMAL_SPEED;
+        let grown = self.snakes[id].body.len().saturating_sub(INITIAL_LENGTH);
+        if let Some(slow_down) = grown.checked_div(self.settings.slow_down_every) {
+            speed = speed.saturating_sub(slow_down).max(MIN_SPEED);
+        }
+        if self.effects[id].has(&EffectKind::SpeedBoost) {
+            speed *= 2;
+        }
+        return speed.min(MAX_SPEED);
+    }
+
+    /// Speeds of all snakes
+    pub fn speeds(&self) -> Vec<usize> {
+        return (0..self.snakes.len()).map(|id| self.speed(id)).collect();
     }
 
     /// Play one turn
+    /// The turn is split into `STEPS` steps, snakes move by at most one cell per step depending on
+    /// their speed, and all snakes moving on a step are resolved together
     pub fn play_turn(&mut self) {
-        let old_positions = self.snakes_to_vec();
-
-        self.move_snakes();
+        self.events.clear();
+        self.with_mode(|mode, game| mode.on_turn_start(game));
+        self.decay_food();
+        for id in self.alive_ids() {
+            self.hunger[id] += 1;
+        }
+        for state in self.states.iter_mut() {
+            if let GameState::Respawning { turns } = state {
+                *turns = turns.saturating_sub(1);
+            }
+        }
+        for _ in 0..STEPS {
+            let mut moving = vec![false; self.snakes.len()];
+            for id in self.alive_ids() {
+                self.progress[id] += self.speed(id);
+                if self.progress[id] >= MAX_SPEED {
+                    self.progress[id] -= MAX_SPEED;
+                    moving[id] = true;
+                }
+            }
+            self.play_step(&moving);
+        }
+        self.starve();
 
+        for id in self.alive_ids() {
+            self.stats[id].turns_survived += 1;
+            self.stats[id].length = self.snakes[id].body.len();
+            self.effects[id].tick();
+        }
         for id in 0..self.snakes.len() {
-            match self.check_collisions(&self.snakes[id]) {
-                Collision::BorderOrSnake => self.states[id] = GameState::Lost,
-                Collision::Food => {
-                    self.snakes[id].body = old_positions[id].clone();
-                    self.snakes[id]._grow(self.food.clone());
-                    self.create_food();
-                },
-                _ => (),
+            if self.states[id] == (GameState::Respawning { turns: 0 }) {
+                self.respawn(id);
             }
         }
+        self.spawn_food();
+        self.turn += 1;
+        self.schedule.on_turn(self.turn);
     }
 
     /// Set all states to state value
@@ -156,4 +821,625 @@ impl Game {
         }
         return snakes;
     }
+
+    /// Final ranking of players
+    pub fn standings(&self) -> Vec<Standing> {
+        return standings(&self.stats);
+    }
+
+    /// Legal directions of each snake for next turn, none for eliminated snakes
+    pub fn legal_directions(&self) -> Vec<Vec<Direction>> {
+        return (0..self.snakes.len())
+            .map(|id| if self.is_alive(id) { self.snakes[id].legal_directions(&self.settings.directions) } else { vec![] })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test case: name, snakes (tail first), food, length tiebreak, expected collisions
+    type Case = (&'static str, Vec<(Vec<(u16, u16)>, Direction)>, (u16, u16), bool, Vec<Collision>);
+
+    /// Build a game with given snakes (tail first) and food
+    fn game_with(snakes: Vec<(Vec<(u16, u16)>, Direction)>, food: (u16, u16), length_tiebreak: bool) -> Game {
+        let settings = GameSettings { seed: Some(0), length_tiebreak, ..GameSettings::default() };
+        let mut game = Game::new(snakes.len(), &settings).unwrap();
+        game.snakes = snakes.into_iter()
+            .map(|(body, direction)| Snake::new(body.into_iter().map(|(x, y)| Point { x, y }).collect(), direction))
+            .collect();
+        game.food = vec![Food { position: Point { x: food.0, y: food.1 }, kind: FoodKind::Normal, decay: None }];
+        game.rebuild_grid();
+        game.set_states(GameState::Playing);
+        return game;
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = |seed: u64| -> Vec<Vec<Food>> {
+            let settings = GameSettings { seed: Some(seed), max_food: 5, food_spawn_chance: 0.5, ..GameSettings::default() };
+            let mut game = Game::new(2, &settings).unwrap();
+            game.set_states(GameState::Playing);
+            let mut food = vec![game.food.clone()];
+            for _ in 0..5 {
+                game.play_turn();
+                food.push(game.food.clone());
+            }
+            return food;
+        };
+        assert_eq!(play(42), play(42));
+        assert_ne!(play(42), play(43));
+    }
+
+    #[test]
+    fn shipped_map_can_be_played() {
+        let settings = GameSettings { map: Some(String::from("maps/arena.txt")), ..GameSettings::default() };
+        assert!(settings.validate().is_ok());
+        for nb in 1..=settings.max_clients {
+            assert!(Game::new(nb, &settings).is_ok(), "{} players", nb);
+        }
+
+        // Spawn points too close to the border are rejected
+        let path = std::env::temp_dir().join("snake_border_spawn.txt");
+        std::fs::write(&path, std::fs::read_to_string("maps/arena.txt").unwrap().replace("#..>", "#.>.")).unwrap();
+        let settings = GameSettings { map: Some(path.to_string_lossy().into_owned()), ..settings };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn resolve_moves_rules() {
+        use Direction::*;
+        let cases: Vec<Case> = vec![
+            ("free move",
+                vec![(vec![(5, 5), (6, 5), (7, 5)], Right)], (15, 15), false,
+                vec![Collision::None]),
+            ("border",
+                vec![(vec![(4, 5), (3, 5), (2, 5)], Left)], (15, 15), false,
+                vec![Collision::Border]),
+            ("self collision",
+                vec![(vec![(5, 5), (6, 5), (7, 5), (7, 6), (6, 6)], Up)], (15, 15), false,
+                vec![Collision::Snake(0)]),
+            ("head into body",
+                vec![(vec![(5, 5), (6, 5), (7, 5)], Down), (vec![(6, 6), (7, 6), (8, 6)], Right)], (15, 15), false,
+                vec![Collision::Snake(1), Collision::None]),
+            ("head into moving tail",
+                vec![(vec![(5, 6), (5, 5), (6, 5)], Down), (vec![(6, 6), (6, 7), (7, 7)], Right)], (15, 15), false,
+                vec![Collision::None, Collision::None]),
+            ("head into tail of eating snake",
+                vec![(vec![(5, 6), (5, 5), (6, 5)], Down), (vec![(6, 6), (6, 7), (7, 7)], Right)], (8, 7), false,
+                vec![Collision::Snake(1), Collision::Food]),
+            ("head-on, equal length",
+                vec![(vec![(3, 5), (4, 5), (5, 5)], Right), (vec![(9, 5), (8, 5), (7, 5)], Left)], (15, 15), false,
+                vec![Collision::HeadOn(1), Collision::HeadOn(0)]),
+            ("head-on, equal length with tiebreak",
+                vec![(vec![(3, 5), (4, 5), (5, 5)], Right), (vec![(9, 5), (8, 5), (7, 5)], Left)], (15, 15), true,
+                vec![Collision::HeadOn(1), Collision::HeadOn(0)]),
+            ("head-on, longer without tiebreak",
+                vec![(vec![(2, 5), (3, 5), (4, 5), (5, 5)], Right), (vec![(9, 5), (8, 5), (7, 5)], Left)], (15, 15), false,
+                vec![Collision::HeadOn(1), Collision::HeadOn(0)]),
+            ("head-on, longer with tiebreak",
+                vec![(vec![(2, 5), (3, 5), (4, 5), (5, 5)], Right), (vec![(9, 5), (8, 5), (7, 5)], Left)], (15, 15), true,
+                vec![Collision::None, Collision::HeadOn(0)]),
+            ("tie on food",
+                vec![(vec![(3, 5), (4, 5), (5, 5)], Right), (vec![(9, 5), (8, 5), (7, 5)], Left)], (6, 5), false,
+                vec![Collision::HeadOn(1), Collision::HeadOn(0)]),
+            ("tie on food with tiebreak",
+                vec![(vec![(9, 5), (8, 5), (7, 5)], Left), (vec![(2, 5), (3, 5), (4, 5), (5, 5)], Right)], (6, 5), true,
+                vec![Collision::HeadOn(1), Collision::Food]),
+            ("swapping heads",
+                vec![(vec![(3, 5), (4, 5), (5, 5)], Right), (vec![(8, 5), (7, 5), (6, 5)], Left)], (15, 15), true,
+                vec![Collision::Snake(1), Collision::Snake(0)]),
+            ("three-way head-on",
+                vec![(vec![(3, 5), (4, 5), (5, 5)], Right), (vec![(9, 5), (8, 5), (7, 5)], Left),
+                     (vec![(6, 8), (6, 7), (6, 6)], Up)], (15, 15), false,
+                vec![Collision::HeadOn(1), Collision::HeadOn(0), Collision::HeadOn(0)]),
+        ];
+
+        for (name, snakes, food, length_tiebreak, expected) in cases {
+            let mut game = game_with(snakes, food, length_tiebreak);
+            let moving = vec![true; game.snakes.len()];
+            assert_eq!(game.resolve_moves(&moving).0, expected, "case: {}", name);
+        }
+
+        // A snake dying with its corpse kept doesn't free its tail
+        let snakes = vec![(vec![(5, 6), (5, 5), (6, 5)], Down), (vec![(6, 6), (6, 7), (7, 7)], Right)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.board.walls.insert(Point { x: 8, y: 7 });
+        game.rebuild_grid();
+        assert_eq!(game.resolve_moves(&[true, true]), (vec![Collision::None, Collision::Wall], vec![false, true]));
+        game.settings.keep_corpses = true;
+        assert_eq!(game.resolve_moves(&[true, true]), (vec![Collision::Snake(1), Collision::Wall], vec![true, true]));
+        game.play_turn();
+        assert_eq!(game.states, vec![GameState::Lost, GameState::Lost]);
+        assert_eq!(*game.grid.get(&Point { x: 6, y: 6 }), Cell::Snake(1));
+    }
+
+    #[test]
+    fn play_turn_is_order_independent() {
+        use Direction::*;
+        let a = (vec![(3, 5), (4, 5), (5, 5)], Right);
+        let b = (vec![(9, 5), (8, 5), (7, 5)], Left);
+        for snakes in [vec![a.clone(), b.clone()], vec![b, a]] {
+            let mut game = game_with(snakes, (6, 5), false);
+            game.play_turn();
+            assert!(game.states.iter().all(|s| matches!(s, GameState::Lost)));
+            assert_eq!(game.food[0].position, Point { x: 6, y: 5 });
+        }
+    }
+
+    #[test]
+    fn eliminated_snakes_leave_the_board() {
+        use Direction::*;
+        let snakes = vec![(vec![(4, 5), (3, 5), (2, 5)], Left), (vec![(5, 9), (6, 9), (7, 9)], Right)];
+        let mut game = game_with(snakes.clone(), (15, 15), false);
+        game.play_turn();
+        assert!(game.snakes[0].body.is_empty());
+        assert_eq!(game.alive_ids(), vec![1]);
+        assert!(game.is_over());
+
+        game = game_with(snakes, (15, 15), false);
+        game.settings.keep_corpses = true;
+        game.settings.survivors = 0;
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.len(), 3);
+        assert!(!game.is_over());
+
+        // Running into a corpse, even one left in the same step, gives no kill
+        let dying = (vec![(4, 5), (3, 5), (2, 5)], Left);
+        let running = (vec![(3, 8), (3, 7), (3, 6)], Up);
+        for (snakes, dead, runner) in [(vec![dying.clone(), running.clone()], 0, 1), (vec![running, dying], 1, 0)] {
+            let mut game = game_with(snakes, (15, 15), false);
+            game.settings.keep_corpses = true;
+            game.play_turn();
+            assert_eq!(game.stats[runner].cause_of_death, Some(DeathCause::Corpse));
+            assert_eq!((game.stats[dead].kills, game.stats[dead].score), (0, 0));
+        }
+    }
+
+    #[test]
+    fn stats_and_standings() {
+        use Direction::*;
+        let snakes = vec![
+            (vec![(5, 5), (6, 5), (7, 5)], Down),
+            (vec![(6, 6), (7, 6), (8, 6)], Right),
+            (vec![(5, 12), (6, 12), (7, 12)], Right),
+        ];
+        let mut game = game_with(snakes, (9, 6), false);
+        game.play_turn();
+
+        assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::Snake { killer: 1 }));
+        assert_eq!(game.stats[1].kills, 1);
+        assert_eq!(game.stats[1].food_eaten, 1);
+        assert_eq!(game.stats[1].length, 4);
+        assert_eq!(game.stats[1].score, KILL_POINTS + FOOD_POINTS);
+        assert_eq!(game.stats[2].turns_survived, 1);
+
+        let ranking: Vec<(usize, usize)> = game.standings().iter().map(|s| (s.rank, s.id)).collect();
+        assert_eq!(ranking, vec![(1, 1), (2, 2), (3, 0)]);
+    }
+
+    #[test]
+    fn food_kinds() {
+        let cases = vec![
+            (FoodKind::Normal, 5, Some(FOOD_POINTS)),
+            (FoodKind::Bonus, 5, Some(BONUS_POINTS)),
+            (FoodKind::Shrink, 3, Some(0)),
+            (FoodKind::Poison, 0, None),
+        ];
+        for (kind, length, score) in cases {
+            let mut game = game_with(vec![(vec![(4, 5), (5, 5), (6, 5), (7, 5)], Direction::Right)], (8, 5), false);
+            game.food[0].kind = kind.clone();
+            game.play_turn();
+            assert_eq!(game.snakes[0].body.len(), length, "kind: {:?}", kind);
+            match score {
+                Some(score) => assert_eq!(game.stats[0].score, score, "kind: {:?}", kind),
+                None => assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::Poison)),
+            }
+        }
+    }
+
+    #[test]
+    fn walls_are_deadly() {
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Direction::Right)], (15, 15), false);
+        game.board.walls.insert(Point { x: 8, y: 5 });
+        game.rebuild_grid();
+        assert_eq!(game.resolve_moves(&[true]).0, vec![Collision::Wall]);
+    }
+
+    #[test]
+    fn food_stops_when_board_is_full() {
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Direction::Right)], (15, 15), false);
+        let free = game.grid.free_count();
+        for _ in 0..free + 10 {
+            game.create_food();
+        }
+        assert_eq!(game.food.len(), free + 1);
+        assert_eq!(game.grid.free_count(), 0);
+
+        // Cells are given back when the snake moves
+        game.food.clear();
+        game.rebuild_grid();
+        game.play_turn();
+        assert_ne!(game.grid.get(&Point { x: 5, y: 5 }), &Cell::Snake(0));
+        assert_eq!(game.grid.get(&Point { x: 8, y: 5 }), &Cell::Snake(0));
+        assert_eq!(game.grid.free_count(), free + 1 - game.food.len());
+    }
+
+    #[test]
+    fn wrapping_topology() {
+        let mut game = game_with(vec![(vec![(4, 2), (3, 2), (2, 2)], Direction::Left)], (15, 15), false);
+        game.board.topology = Topology::Wrapping;
+        game.play_turn();
+        assert!(matches!(game.states[0], GameState::Playing));
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 19, y: 2 }));
+
+        game.snakes[0].direction = Direction::Up;
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 19, y: 19 }));
+    }
+
+    #[test]
+    fn portals() {
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Direction::Right)], (15, 15), false);
+        game.board.portals.insert(Point { x: 8, y: 5 }, Point { x: 12, y: 10 });
+        game.board.portals.insert(Point { x: 12, y: 10 }, Point { x: 8, y: 5 });
+        game.rebuild_grid();
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 12, y: 10 }));
+        assert_eq!(game.snakes[0].heading, Direction::Right);
+
+        // The body follows through
+        game.play_turn();
+        game.play_turn();
+        assert_eq!(game.snakes[0].body, vec![Point { x: 12, y: 10 }, Point { x: 13, y: 10 }, Point { x: 14, y: 10 }]);
+        assert!(matches!(game.states[0], GameState::Playing));
+
+        // Nothing can stay on a portal, food never spawns there
+        game.snakes[0].body = vec![Point { x: 2, y: 2 }, Point { x: 3, y: 2 }, Point { x: 4, y: 2 }];
+        game.rebuild_grid();
+        for _ in 0..game.grid.free_count() + 10 {
+            game.create_food();
+        }
+        assert!(game.food.iter().all(|f| !game.board.is_portal(&f.position)));
+        assert!(!game.grid.is_free(&Point { x: 8, y: 5 }));
+    }
+
+    #[test]
+    fn diagonal_moves() {
+        use Direction::*;
+        let through_body = vec![(vec![(3, 5), (4, 5), (5, 5)], DownRight), (vec![(7, 4), (6, 5), (5, 6)], DownLeft)];
+        let crossing_heads = vec![(vec![(3, 5), (4, 5), (5, 5)], DownRight), (vec![(8, 5), (7, 5), (6, 5)], DownLeft)];
+
+        let mut game = game_with(through_body.clone(), (15, 15), false);
+        assert_eq!(game.resolve_moves(&[true, true]).0, vec![Collision::Snake(1), Collision::None]);
+        let mut game = game_with(crossing_heads.clone(), (15, 15), false);
+        assert_eq!(game.resolve_moves(&[true, true]).0, vec![Collision::Snake(1), Collision::Snake(0)]);
+
+        for snakes in [through_body, crossing_heads] {
+            let mut game = game_with(snakes, (15, 15), false);
+            game.settings.diagonal_crossing = true;
+            game.play_turn();
+            assert_eq!(game.alive_ids(), vec![0, 1]);
+            assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 6, y: 6 }));
+        }
+    }
+
+    #[test]
+    fn play_turn_grows_eating_snake() {
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Direction::Right)], (8, 5), false);
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.len(), 4);
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 8, y: 5 }));
+        assert_eq!(game.food.len(), 1);
+        assert_ne!(game.food[0].position, Point { x: 8, y: 5 });
+        assert!(matches!(game.states[0], GameState::Playing));
+    }
+
+    #[test]
+    fn game_modes() {
+        use Direction::*;
+        let snakes = vec![(vec![(5, 5), (6, 5), (7, 5)], Right), (vec![(5, 9), (6, 9), (7, 9)], Right)];
+
+        let mut game = game_with(snakes.clone(), (8, 5), false);
+        game.mode = ModeKind::TimeLimited { turns: 2 }.create();
+        game.play_turn();
+        assert_eq!(game.winners(), None);
+        game.play_turn();
+        assert_eq!(game.winners(), Some(vec![0]));
+
+        let mut game = game_with(snakes.clone(), (8, 5), false);
+        game.mode = ModeKind::FirstToLength { length: 4 }.create();
+        assert_eq!(game.winners(), None);
+        game.play_turn();
+        assert_eq!(game.winners(), Some(vec![0]));
+
+        let mut game = game_with(snakes, (15, 15), false);
+        game.mode = ModeKind::Endless.create();
+        game.eliminate(1, DeathCause::Border);
+        assert_eq!(game.winners(), None);
+        game.eliminate(0, DeathCause::Border);
+        assert_eq!(game.winners(), Some(vec![]));
+    }
+
+    #[test]
+    fn battle_royale_shrinks_arena() {
+        use Direction::*;
+        let snakes = vec![(vec![(2, 10), (2, 9), (2, 8)], Up), (vec![(8, 10), (9, 10), (10, 10)], Right)];
+        let mut game = game_with(snakes, (19, 15), false);
+        game.mode = ModeKind::BattleRoyale { interval: 2 }.create();
+        let next = Zone { min: Point { x: 3, y: 3 }, max: Point { x: 18, y: 18 } };
+        assert_eq!(game.upcoming_zone(), Some(ZoneWarning { zone: next.clone(), turns: 2 }));
+        game.play_turn();
+        game.play_turn();
+        assert_eq!(game.upcoming_zone().map(|z| z.turns), Some(0));
+        assert!(matches!(game.states[0], GameState::Playing));
+
+        game.play_turn();
+        assert_eq!(game.board.zone(game.board.shrink), next);
+        assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::Border));
+        assert!(game.food.iter().all(|f| !game.board.is_border(&f.position)));
+        assert_eq!(*game.grid.get(&Point { x: 2, y: 10 }), Cell::Wall);
+        assert_eq!(game.winners(), Some(vec![1]));
+
+        // The zone stops shrinking at its minimal size
+        game.board.shrink = 7;
+        assert!(!game.board.can_shrink());
+        assert_eq!(game.upcoming_zone(), None);
+    }
+
+    #[test]
+    fn power_ups() {
+        use Direction::*;
+        // Speed boost: the snake moves twice per turn once it picked it up
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Right)], (8, 5), false);
+        game.food[0].kind = FoodKind::SpeedBoost;
+        game.play_turn();
+        assert_eq!(game.effects[0], Effects(vec![Effect { kind: EffectKind::SpeedBoost, turns: SPEED_BOOST_TURNS - 1 }]));
+        assert_eq!(game.stats[0].food_eaten, 0);
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 10, y: 5 }));
+        assert_eq!(game.snakes[0].body.len(), 3);
+
+        // Ghost: the snake goes through another one, which keeps its cells
+        let snakes = vec![(vec![(5, 5), (6, 5), (7, 5)], Right), (vec![(8, 3), (8, 4), (8, 5)], Down)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.effects[0].add(EffectKind::Ghost);
+        game.play_turn();
+        assert_eq!(game.alive_ids(), vec![0, 1]);
+        assert_eq!(*game.grid.get(&Point { x: 8, y: 5 }), Cell::Snake(1));
+        game.play_turn();
+        game.play_turn();
+        assert_eq!(*game.grid.get(&Point { x: 8, y: 5 }), Cell::Snake(0));
+
+        // Shield: the snake survives one collision
+        let mut game = game_with(vec![(vec![(4, 5), (3, 5), (2, 5)], Left)], (15, 15), false);
+        game.effects[0].add(EffectKind::Shield);
+        game.play_turn();
+        assert!(matches!(game.states[0], GameState::Playing));
+        assert_eq!(game.effects[0], Effects::default());
+        game.play_turn();
+        assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::Border));
+
+        // A shielded snake stays in place, its tail keeps blocking
+        let snakes = vec![(vec![(5, 6), (5, 5), (6, 5)], Down), (vec![(6, 6), (6, 7), (7, 7)], Right)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.board.walls.insert(Point { x: 8, y: 7 });
+        game.rebuild_grid();
+        game.effects[1].add(EffectKind::Shield);
+        game.play_turn();
+        assert_eq!(game.states, vec![GameState::Lost, GameState::Playing]);
+        assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::Snake { killer: 1 }));
+        assert_eq!(*game.grid.get(&Point { x: 6, y: 6 }), Cell::Snake(1));
+    }
+
+    #[test]
+    fn variable_speeds() {
+        use Direction::*;
+        // Long snakes slow down, down to one cell every other tick here
+        let mut game = game_with(vec![(vec![(3, 5), (4, 5), (5, 5), (6, 5), (7, 5)], Right)], (15, 15), false);
+        game.settings.slow_down_every = 1;
+        assert_eq!(game.speed(0), NORMAL_SPEED / 2);
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 7, y: 5 }));
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 8, y: 5 }));
+
+        // A fast snake meets a normal one on the step they reach the same cell
+        let snakes = vec![(vec![(5, 5), (6, 5), (7, 5)], Right), (vec![(9, 8), (9, 7), (9, 6)], Up)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.effects[0].add(EffectKind::SpeedBoost);
+        game.play_turn();
+        assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::HeadOn { killer: 1 }));
+        assert_eq!(game.stats[1].cause_of_death, Some(DeathCause::HeadOn { killer: 0 }));
+        assert_eq!(game.snakes[0].body.len(), 0);
+    }
+
+    #[test]
+    fn corpses_turn_into_food() {
+        use Direction::*;
+        let snakes = vec![(vec![(4, 5), (3, 5), (2, 5)], Left), (vec![(4, 9), (4, 8), (4, 7)], Up)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.settings.corpse_food = true;
+        game.settings.corpse_decay = 2;
+        let corpses = |game: &Game| game.food.iter().filter(|f| f.kind == FoodKind::Corpse).count();
+        game.play_turn();
+        assert_eq!(corpses(&game), 3);
+        assert_eq!(*game.grid.get(&Point { x: 3, y: 5 }), Cell::Food);
+
+        game.play_turn();
+        assert_eq!(game.snakes[1].body.len(), 4);
+        assert_eq!((game.stats[1].food_eaten, game.stats[1].score), (1, 0));
+        game.play_turn();
+        assert_eq!(corpses(&game), 2);
+        game.play_turn();
+        assert_eq!(corpses(&game), 0);
+        assert!(game.grid.is_free(&Point { x: 3, y: 5 }));
+    }
+
+    #[test]
+    fn tail_cutting() {
+        use Direction::*;
+        let snakes = vec![
+            (vec![(7, 4), (7, 5), (7, 6)], Down),
+            (vec![(5, 7), (6, 7), (7, 7), (8, 7), (9, 7), (10, 7)], Right),
+        ];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.settings.tail_cutting = true;
+        game.play_turn();
+        assert_eq!(game.alive_ids(), vec![0, 1]);
+        assert_eq!(game.snakes[1].body.len(), 4);
+        assert_eq!(game.stats[0].score, 2 * CUT_POINTS);
+        assert_eq!(game.events, vec![GameEvent::Cut { attacker: 0, victim: 1, position: Point { x: 7, y: 7 }, segments: 2 }]);
+        assert_eq!(*game.grid.get(&Point { x: 7, y: 7 }), Cell::Snake(0));
+        assert!(game.grid.is_free(&Point { x: 6, y: 7 }));
+
+        // Too short to be cut
+        let snakes = vec![(vec![(8, 4), (8, 5), (8, 6)], Down), (vec![(6, 7), (7, 7), (8, 7)], Right)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.settings.tail_cutting = true;
+        game.play_turn();
+        assert_eq!(game.stats[1].cause_of_death, Some(DeathCause::Snake { killer: 0 }));
+        assert_eq!(game.stats[0].kills, 1);
+        assert!(game.events.is_empty());
+    }
+
+    #[test]
+    fn hunger() {
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Direction::Right)], (9, 5), false);
+        game.settings.hunger = 2;
+        assert_eq!(game.hunger(), vec![Some(2)]);
+        game.play_turn();
+        game.play_turn();
+        // Ate on the second turn
+        assert_eq!((game.snakes[0].body.len(), game.hunger()), (4, vec![Some(2)]));
+        game.play_turn();
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.len(), 3);
+        assert!(game.grid.is_free(&Point { x: 8, y: 5 }));
+        game.play_turn();
+        game.play_turn();
+        assert_eq!(game.snakes[0].body.len(), MIN_LENGTH);
+        game.play_turn();
+        game.play_turn();
+        assert_eq!(game.stats[0].cause_of_death, Some(DeathCause::Starvation));
+        assert_eq!(game.hunger(), vec![None]);
+    }
+
+    #[test]
+    fn respawn() {
+        use Direction::*;
+        let snakes = vec![(vec![(4, 5), (3, 5), (2, 5)], Left), (vec![(5, 9), (6, 9), (7, 9)], Right)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.settings.lives = 2;
+        game.settings.respawn_delay = 2;
+        game.play_turn();
+        assert_eq!(game.states[0], GameState::Respawning { turns: 2 });
+        assert!(game.snakes[0].body.is_empty());
+        assert_eq!(game.winners(), None);
+        game.play_turn();
+        assert_eq!(game.states[0], GameState::Respawning { turns: 1 });
+        game.play_turn();
+        assert_eq!(game.states[0], GameState::Playing);
+        assert_eq!(game.snakes[0].body.len(), INITIAL_LENGTH);
+        assert!(game.snakes[0].body.iter().all(|p| *game.grid.get(p) == Cell::Snake(0)));
+        assert_eq!((game.stats[0].deaths, &game.stats[0].cause_of_death), (1, &None));
+
+        // Out of lives
+        game.eliminate(0, DeathCause::Border);
+        assert_eq!(game.states[0], GameState::Lost);
+        assert_eq!(game.winners(), Some(vec![1]));
+    }
+
+    #[test]
+    fn teams() {
+        use Direction::*;
+        let snakes = vec![
+            (vec![(5, 5), (6, 5), (7, 5)], Right),
+            (vec![(5, 12), (6, 12), (7, 12)], Right),
+            (vec![(8, 3), (8, 4), (8, 5)], Down),
+            (vec![(5, 15), (6, 15), (7, 15)], Right),
+        ];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.settings.teams = 2;
+        game.settings.friendly_fire = false;
+        game.assign_teams(&[Some(1), None, Some(1), Some(5)]);
+        assert_eq!(game.teams, vec![1, 0, 1, 0]);
+
+        // Teammates go through each other
+        assert_eq!(game.resolve_moves(&[true, false, false, false]).0, vec![Collision::None; 4]);
+        game.settings.friendly_fire = true;
+        assert_eq!(game.resolve_moves(&[true, false, false, false]).0[0], Collision::Snake(2));
+
+        // The whole team wins, eliminated players included
+        game.eliminate(1, DeathCause::Border);
+        game.eliminate(2, DeathCause::Border);
+        assert_eq!(game.winners(), None);
+        game.eliminate(3, DeathCause::Border);
+        assert_eq!(game.winners(), Some(vec![0, 2]));
+
+        game.mode = ModeKind::TimeLimited { turns: 0 }.create();
+        game.stats[1].score = 3;
+        game.stats[2].score = 2;
+        assert_eq!(game.team_score(1), 2);
+        assert_eq!(game.winners(), Some(vec![1, 3]));
+    }
+
+    #[test]
+    fn cooperative_mode() {
+        let mut game = game_with(vec![(vec![(5, 5), (6, 5), (7, 5)], Direction::Right)], (8, 5), false);
+        game.mode = ModeKind::Cooperative { food: 2, turns: 5, obstacle_every: 1 }.create();
+        game.play_turn();
+        assert_eq!(game.objective(), Some(Objective { progress: 1, goal: 2, turns_left: 4 }));
+        assert_eq!(game.winners(), None);
+        assert!(game.obstacles.is_empty());
+
+        game.play_turn();
+        assert_eq!(game.obstacles.len(), 1);
+        assert_eq!(*game.grid.get(&game.obstacles[0]), Cell::Wall);
+        game.turn = 5;
+        assert_eq!(game.winners(), Some(vec![]));
+        game.mode = ModeKind::Cooperative { food: 1, turns: 5, obstacle_every: 1 }.create();
+        game.food = vec![Food { position: Point { x: 10, y: 5 }, kind: FoodKind::Normal, decay: None }];
+        game.rebuild_grid();
+        game.turn = 0;
+        game.play_turn();
+        assert_eq!(game.winners(), Some(vec![0]));
+    }
+
+    /// Mode where snakes bounce off walls and borders
+    struct Bouncing;
+    impl GameMode for Bouncing {
+        fn kind(&self) -> ModeKind {
+            return ModeKind::Endless;
+        }
+
+        fn on_collision(&mut self, _game: &mut Game, _id: usize, collision: &Collision) -> bool {
+            return !matches!(collision, Collision::Border | Collision::Wall);
+        }
+
+        fn winners(&self, _game: &Game) -> Option<Vec<usize>> {
+            return None;
+        }
+    }
+
+    #[test]
+    fn mode_decides_if_collisions_are_fatal() {
+        let mut game = game_with(vec![(vec![(4, 5), (3, 5), (2, 5)], Direction::Left)], (15, 15), false);
+        game.mode = Box::new(Bouncing);
+        game.play_turn();
+        assert!(matches!(game.states[0], GameState::Playing));
+        assert_eq!(game.snakes[0].body.last(), Some(&Point { x: 2, y: 5 }));
+        assert_eq!(game.turn, 1);
+
+        // A snake surviving a collision doesn't free its tail
+        let snakes = vec![(vec![(5, 6), (5, 5), (6, 5)], Direction::Down), (vec![(6, 6), (6, 7), (7, 7)], Direction::Right)];
+        let mut game = game_with(snakes, (15, 15), false);
+        game.mode = Box::new(Bouncing);
+        game.board.walls.insert(Point { x: 8, y: 7 });
+        game.rebuild_grid();
+        game.play_turn();
+        assert_eq!(game.states, vec![GameState::Lost, GameState::Playing]);
+        assert_eq!(game.snakes[1].body.first(), Some(&Point { x: 6, y: 6 }));
+    }
 }
@@ -0,0 +1,210 @@
+use crate::game::*;
+use crate::board::*;
+use crate::map::*;
+use crate::spawn::*;
+use crate::settings::*;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use std::collections::HashSet;
+
+// Maps that can't be validated are generated again with another seed, this many times at most
+const MAX_ATTEMPTS: u64 = 20;
+// Share of the cells inside the border taken by walls in scattered maps, in percent
+const SCATTER_DENSITY: u32 = 12;
+
+/// Layouts of generated maps
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum MapStyle {
+    Scatter, // Walls scattered at random
+    Maze, // Corridors two cells wide, with a few loops
+    Rooms, // Rooms linked by doors
+}
+impl FromStr for MapStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "scatter" => Ok(MapStyle::Scatter),
+            "maze" => Ok(MapStyle::Maze),
+            "rooms" => Ok(MapStyle::Rooms),
+            _ => Err(format!("Unknown map style {}", s)),
+        }
+    }
+}
+
+/// Seeded map generator
+/// Generated maps come with spawn points for `max_clients` snakes, which can all reach each other,
+/// and at least half of the cells inside the border are free
+pub struct MapGenerator {
+    width: usize,
+    height: usize,
+    walls: HashSet<Point>,
+    rng: StdRng,
+}
+impl MapGenerator {
+    /// Create new MapGenerator, with walls everywhere inside the border
+    fn new(width: usize, height: usize, seed: u64) -> Self {
+        let mut walls: HashSet<Point> = HashSet::new();
+        for y in 2..height as u16 {
+            for x in 2..width as u16 {
+                walls.insert(Point { x, y });
+            }
+        }
+        MapGenerator { width, height, walls, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    /// Generate a map for given settings
+    pub fn generate(style: &MapStyle, settings: &GameSettings, seed: u64) -> Result<Map, String> {
+        // Check the size first, generating on smaller boards would overflow
+        Map::empty(settings.width, settings.height).validate()?;
+        let mut error = String::new();
+        for attempt in 0..MAX_ATTEMPTS {
+            let mut generator = MapGenerator::new(settings.width, settings.height, seed.wrapping_add(attempt));
+            match style {
+                MapStyle::Scatter => generator.scatter(),
+                MapStyle::Maze => generator.partition(3, 2, 0.15)?,
+                MapStyle::Rooms => generator.partition(8, 2, 0.3)?,
+            }
+            match generator.finish(settings) {
+                Ok(map) => return Ok(map),
+                Err(e) => error = e,
+            }
+        }
+        return Err(format!("Could not generate a {:?} map: {}", style, error));
+    }
+
+    /// Scatter single walls over an empty field
+    fn scatter(&mut self) {
+        let inside = self.walls.len();
+        self.walls.clear();
+        while self.walls.len() * 100 < inside * SCATTER_DENSITY as usize {
+            let x = self.rng.gen_range(2..self.width as u16);
+            let y = self.rng.gen_range(2..self.height as u16);
+            self.walls.insert(Point { x, y });
+        }
+    }
+
+    /// Split the field into square cells of `pitch - 1` cells separated by walls,
+    /// then open doors of `door` cells along a random spanning tree of the cells,
+    /// and between other neighbours with probability `loops`
+    fn partition(&mut self, pitch: usize, door: usize, loops: f64) -> Result<(), String> {
+        let cols = (self.width - 1) / pitch;
+        let rows = (self.height - 1) / pitch;
+        if cols == 0 || rows == 0 {
+            return Err(format!("A {}x{} map is too small for cells of size {}", self.width, self.height, pitch - 1));
+        }
+        let size = pitch - 1;
+        let origin = |i: usize| (2 + i * pitch) as u16;
+        for j in 0..rows {
+            for i in 0..cols {
+                for dy in 0..size as u16 {
+                    for dx in 0..size as u16 {
+                        self.walls.remove(&Point { x: origin(i) + dx, y: origin(j) + dy });
+                    }
+                }
+            }
+        }
+
+        // Depth-first spanning tree
+        let mut visited = vec![false; cols * rows];
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        let mut edges: HashSet<((usize, usize), (usize, usize))> = HashSet::new();
+        visited[0] = true;
+        while let Some(&(i, j)) = stack.last() {
+            let mut next: Vec<(usize, usize)> = vec![];
+            if i > 0 { next.push((i - 1, j)); }
+            if i + 1 < cols { next.push((i + 1, j)); }
+            if j > 0 { next.push((i, j - 1)); }
+            if j + 1 < rows { next.push((i, j + 1)); }
+            next.retain(|&(ni, nj)| !visited[nj * cols + ni]);
+            match next.choose(&mut self.rng) {
+                Some(&(ni, nj)) => {
+                    visited[nj * cols + ni] = true;
+                    edges.insert(((i, j).min((ni, nj)), (i, j).max((ni, nj))));
+                    stack.push((ni, nj));
+                },
+                None => { stack.pop(); },
+            }
+        }
+
+        for j in 0..rows {
+            for i in 0..cols {
+                for (ni, nj) in [(i + 1, j), (i, j + 1)] {
+                    if ni >= cols || nj >= rows {
+                        continue;
+                    }
+                    if !edges.contains(&((i, j), (ni, nj))) && !self.rng.gen_bool(loops) {
+                        continue;
+                    }
+                    let offset = self.rng.gen_range(0..=size - door.min(size)) as u16;
+                    for k in 0..door.min(size) as u16 {
+                        let p = match ni > i {
+                            true => Point { x: origin(i) + size as u16, y: origin(j) + offset + k },
+                            false => Point { x: origin(i) + offset + k, y: origin(j) + size as u16 },
+                        };
+                        self.walls.remove(&p);
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Place spawn points, fill the parts of the map they can't reach and validate the map
+    fn finish(self, settings: &GameSettings) -> Result<Map, String> {
+        let mut map = Map::empty(self.width, self.height);
+        map.walls = self.walls.into_iter().collect();
+        map.walls.sort_by_key(|p| (p.y, p.x));
+        let board = Board::from_map(&map, settings.topology.clone());
+        map.spawns = SpawnPlanner::plan(&board, settings.max_clients, settings.spawn_clearance, &settings.spawn_layout)?;
+
+        let reachable = map.reachable(&map.spawns[0].position);
+        for y in 2..self.height as u16 {
+            for x in 2..self.width as u16 {
+                let p = Point { x, y };
+                if !map.is_wall(&p) && !reachable.contains(&p) {
+                    map.walls.push(p);
+                }
+            }
+        }
+        map.validate()?;
+        map.check_connected((self.width - 2) * (self.height - 2) / 2)?;
+        return Ok(map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_maps_are_connected() {
+        for style in [MapStyle::Scatter, MapStyle::Maze, MapStyle::Rooms] {
+            for seed in 0..5 {
+                let settings = GameSettings { width: 30, height: 24, ..GameSettings::default() };
+                let map = MapGenerator::generate(&style, &settings, seed).unwrap();
+                assert_eq!(map.spawns.len(), settings.max_clients);
+                assert!(map.check_connected(0).is_ok(), "{:?} map with seed {}", style, seed);
+                // Same seed, same map
+                assert_eq!(map.walls, MapGenerator::generate(&style, &settings, seed).unwrap().walls);
+            }
+        }
+
+        // Rooms don't fit on the smallest maps
+        let settings = GameSettings { width: 8, height: 8, ..GameSettings::default() };
+        assert!(MapGenerator::generate(&MapStyle::Rooms, &settings, 0).is_err());
+        // Games tell which seed made their map
+        let settings = GameSettings { generator: Some(MapStyle::Maze), seed: Some(3), ..GameSettings::default() };
+        let game = Game::new(2, &settings).unwrap();
+        assert_eq!((game.map_seed, &game.map_error), (Some(3), &None));
+        assert_eq!(game.map.walls, MapGenerator::generate(&MapStyle::Maze, &settings, 3).unwrap().walls);
+
+        for style in [MapStyle::Scatter, MapStyle::Maze, MapStyle::Rooms] {
+            let settings = GameSettings { width: 0, ..GameSettings::default() };
+            assert!(MapGenerator::generate(&style, &settings, 0).is_err());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::game::*;
+use crate::board::*;
+use rand::Rng;
+
+/// What occupies a cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Wall, // Walls and border
+    Snake(usize),
+    Food,
+}
+
+/// Occupancy grid of the board
+/// Free cells (empty, inside the border and off portals) are also kept in a list,
+/// so that a random one can be picked in constant time
+pub struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    free: Vec<Point>,
+    free_index: Vec<Option<usize>>, // Position of each cell in free
+    reserved: Vec<bool>, // Empty cells never free, such as portals, as nothing can stay on them
+    segments: Vec<usize>, // Number of snake segments on each cell, more than one where snakes overlap
+}
+impl Grid {
+    /// Create a grid with the walls and border of a board
+    pub fn new(board: &Board) -> Self {
+        let size = (board.width + 1) * (board.height + 1);
+        let mut grid = Grid {
+            width: board.width,
+            height: board.height,
+            cells: vec![Cell::Wall; size],
+            free: vec![],
+            free_index: vec![None; size],
+            reserved: vec![false; size],
+            segments: vec![0; size],
+        };
+        for y in 1..=board.height {
+            for x in 1..=board.width {
+                let p = Point { x: x as u16, y: y as u16 };
+                if !board.is_border(&p) && !board.is_wall(&p) {
+                    let i = grid.index(&p).unwrap();
+                    grid.reserved[i] = board.is_portal(&p);
+                    grid.set(&p, Cell::Empty);
+                }
+            }
+        }
+        return grid;
+    }
+
+    /// Index of a point in cells, if it is on the board
+    fn index(&self, p: &Point) -> Option<usize> {
+        let (x, y) = (p.x as usize, p.y as usize);
+        if x > self.width || y > self.height {
+            return None;
+        }
+        return Some(y * (self.width + 1) + x);
+    }
+
+    /// Content of a cell, points outside the board are walls
+    pub fn get(&self, p: &Point) -> &Cell {
+        match self.index(p) {
+            Some(i) => &self.cells[i],
+            None => &Cell::Wall,
+        }
+    }
+
+    /// Check if a cell is free
+    pub fn is_free(&self, p: &Point) -> bool {
+        return matches!(self.index(p), Some(i) if self.cells[i] == Cell::Empty && !self.reserved[i]);
+    }
+
+    /// Change the content of a cell
+    pub fn set(&mut self, p: &Point, cell: Cell) {
+        let i = match self.index(p) {
+            Some(i) => i,
+            None => return,
+        };
+        match (self.free_index[i], cell == Cell::Empty && !self.reserved[i]) {
+            (None, true) => {
+                self.free_index[i] = Some(self.free.len());
+                self.free.push(p.clone());
+            },
+            (Some(j), false) => {
+                self.free.swap_remove(j);
+                if let Some(moved) = self.free.get(j).cloned() {
+                    let k = self.index(&moved).unwrap();
+                    self.free_index[k] = Some(j);
+                }
+                self.free_index[i] = None;
+            },
+            _ => (),
+        }
+        self.cells[i] = cell;
+    }
+
+    /// Empty a cell if it holds a given content
+    pub fn clear(&mut self, p: &Point, cell: &Cell) {
+        if self.get(p) == cell {
+            self.set(p, Cell::Empty);
+        }
+    }
+
+    /// Count one more snake segment on a cell
+    pub fn add_segment(&mut self, p: &Point) {
+        if let Some(i) = self.index(p) {
+            self.segments[i] += 1;
+        }
+    }
+
+    /// Count one less snake segment on a cell, returns the number of segments left there
+    pub fn remove_segment(&mut self, p: &Point) -> usize {
+        let i = match self.index(p) {
+            Some(i) => i,
+            None => return 0,
+        };
+        self.segments[i] = self.segments[i].saturating_sub(1);
+        return self.segments[i];
+    }
+
+    /// Number of free cells
+    pub fn free_count(&self) -> usize {
+        return self.free.len();
+    }
+
+    /// Pick a random free cell
+    pub fn random_free<R: Rng>(&self, rng: &mut R) -> Option<Point> {
+        if self.free.is_empty() {
+            return None;
+        }
+        return Some(self.free[rng.gen_range(0..self.free.len())].clone());
+    }
+}
@@ -1,10 +1,29 @@
+#![allow(clippy::needless_return, clippy::needless_range_loop, clippy::result_unit_err)]
+
 pub mod game;
 pub mod snake;
 pub mod connection;
+pub mod settings;
+pub mod stats;
+pub mod food;
+pub mod board;
+pub mod map;
+pub mod grid;
+pub mod spawn;
+pub mod mode;
+pub mod effect;
+pub mod schedule;
+pub mod generator;
 
 use game::*;
 use snake::*;
 use connection::*;
+use settings::*;
+use stats::*;
+use food::*;
+use board::*;
+use map::*;
+use mode::*;
 
 use std::net::{TcpListener, TcpStream};
 use std::io::{Write, BufReader, BufWriter};
@@ -14,11 +33,10 @@ use std::sync::mpsc::{Sender, Receiver, channel, TryRecvError};
 use std::time::Duration;
 use chrono::{Utc, Timelike};
 use std::fs::{File, OpenOptions};
+use std::env;
 
 // Log file
-const LOG_FILE: &'static str = "log";
-// Max number of clients in a game
-const MAX_CLIENTS: usize = 4;
+const LOG_FILE: &str = "log";
 
 /// Channels
 struct Channels {
@@ -31,16 +49,38 @@ struct Channels {
 pub struct GameConfig {
     width: usize,
     height: usize,
+    mode: ModeKind,
+    topology: Topology,
+    map: Map,
     snakes: Vec<Vec<Point>>,
-    food: Point,
+    food: Vec<Food>,
+    speed: usize,
+    max_clients: usize,
+    seed: u64,
+    map_seed: Option<u64>,
+    teams: Vec<usize>,
+    directions: DirectionSet,
+    diagonal_crossing: bool,
+    legal_directions: Vec<Vec<Direction>>,
 }
 impl GameConfig {
     pub fn new(game: &Game) -> Self {
         let config = GameConfig {
-            width: game.width,
-            height: game.height,
+            width: game.board.width,
+            height: game.board.height,
+            mode: game.mode(),
+            topology: game.board.topology.clone(),
+            map: game.map.clone(),
             snakes: game.snakes_to_vec(),
             food: game.food.clone(),
+            speed: game.settings.speed,
+            max_clients: game.settings.max_clients,
+            seed: game.seed,
+            map_seed: game.map_seed,
+            teams: game.teams.clone(),
+            directions: game.settings.directions.clone(),
+            diagonal_crossing: game.settings.diagonal_crossing,
+            legal_directions: game.legal_directions(),
         };
         return config;
     }
@@ -54,7 +94,10 @@ enum ClientEvent {
     SendNewTurn,
     WaitDirection,
     SendTurnResult(TurnData),
+    SendEvents(Vec<GameEvent>),
     SendClientGameState(StateData),
+    SendError(String),
+    SendGameOver(Vec<usize>, Vec<Standing>),
 }
 /// Client events messages sent from Game thread to client threads
 struct ClientEventMessage {
@@ -65,6 +108,7 @@ struct ClientEventMessage {
 enum ClientMessage {
     Direction(snake::Direction),
     StartGame,
+    JoinTeam(usize),
 }
 
 /// Log function
@@ -72,7 +116,7 @@ fn log(s: &str) {
     if let Ok(mut file) = OpenOptions::new().append(true).open(LOG_FILE) {
         let now = Utc::now();
         let line = format!("[{}:{}:{}] {}\n", now.hour(), now.minute(), now.second(), s);
-        file.write(line.as_bytes()).unwrap();
+        file.write_all(line.as_bytes()).unwrap();
     }
 }
 
@@ -85,9 +129,8 @@ fn remove_players(mut ids: Vec<usize>, channels: &mut Channels, game: &mut Game)
         channels.senders.remove(id);
         channels.receivers.remove(id);
         channels.size -= 1;
-        // Remove states and snakes for this player
-        game.states.remove(id);
-        game.snakes.remove(id);
+        // Remove states, snakes and stats for this player
+        game.remove_player(id);
         // Update other id
         // (if they are > id, they need -1 since entries have been deleted)
         for j in 0..ids.len() {
@@ -98,69 +141,78 @@ fn remove_players(mut ids: Vec<usize>, channels: &mut Channels, game: &mut Game)
     }
 }
 
-/// Send event to all client threads
-fn send_all(event: ClientEvent, channels: &mut Channels, game: &mut Game) {
-    let mut ids: Vec<usize> = vec![];
-    let mut id = 0;
-    for sender in channels.senders.iter() {
-        match sender.send(ClientEventMessage { event: event.clone(), id }) {
+/// Send event to some client threads knowing their id
+fn send_to(ids: &[usize], event: ClientEvent, channels: &mut Channels, game: &mut Game) {
+    let mut closed: Vec<usize> = vec![];
+    for &id in ids {
+        match channels.senders[id].send(ClientEventMessage { event: event.clone(), id }) {
             Ok(()) => (),
             Err(_) => {
                 log(&format!("Client {} closed connection, it will be removed from the pool", id));
-                ids.push(id);
+                closed.push(id);
             },
         }
-        id += 1;
     }
-    remove_players(ids, channels, game);
+    remove_players(closed, channels, game);
+}
+
+/// Send event to all client threads
+fn send_all(event: ClientEvent, channels: &mut Channels, game: &mut Game) {
+    let ids: Vec<usize> = (0..channels.size).collect();
+    send_to(&ids, event, channels, game);
 }
 
-/// Receive message from all client threads
-fn receive_all(channels: &mut Channels, game: &mut Game) -> Vec<snake::Direction> {
-    let mut messages: Vec<Direction> = vec![];
-    let mut ids: Vec<usize> = vec![];
-    let mut id = 0;
-    for receiver in channels.receivers.iter() {
-        match receiver.recv() {
+/// Receive directions from some client threads knowing their id
+/// Directions are indexed by player id once closed connections are removed,
+/// players that weren't asked get None
+fn receive_from(ids: &[usize], channels: &mut Channels, game: &mut Game) -> Vec<Option<snake::Direction>> {
+    let mut messages: Vec<Option<Direction>> = vec![None; channels.size];
+    let mut closed: Vec<usize> = vec![];
+    for &id in ids {
+        match channels.receivers[id].recv() {
             Ok(message) => {
                 match message {
-                    ClientMessage::Direction(direction) => messages.push(direction),
+                    ClientMessage::Direction(direction) => messages[id] = Some(direction),
                     _ => panic!("Wrong ClientMessage type received"),
                 }
             },
             Err(_) => {
                 log(&format!("Client {} closed connection, it will be removed from the pool", id));
-                ids.push(id);
+                closed.push(id);
             }
         }
-        id += 1;
     }
-    remove_players(ids, channels, game);
+    for &id in closed.iter().rev() {
+        messages.remove(id);
+    }
+    remove_players(closed, channels, game);
     return messages;
 }
 
 /// Game thread function
-fn game_(rx: Receiver<TcpStream>) {
+fn game_(rx: Receiver<TcpStream>, settings: GameSettings) {
     let _rx = &rx;
     loop {
         let mut channels = Channels { senders: vec![], receivers: vec![], size: 0 };
+        let mut teams: Vec<Option<usize>> = vec![]; // Teams asked by players in the lobby
 
         loop {
             match _rx.try_recv() {
                 Ok(s) => {
                     log(&format!("New client! Connection from: {:?}", s.peer_addr().unwrap()));
-                    if channels.size < MAX_CLIENTS {
+                    if channels.size < settings.max_clients {
                         let (tx_c1, rx_c1) = channel();
                         let (tx_c2, rx_c2) = channel();
                         thread::spawn(move || { handle_client(s, tx_c2, rx_c1); });
                         channels.senders.push(tx_c1);
                         channels.receivers.push(rx_c2);
                         channels.size += 1;
+                        teams.push(None);
                         log(&format!("New client added ! {} clients in the game", channels.size));
                     }
-                    // Handle MAX_CLIENTS clients maximum at a time, so other clients will have to wait,
+                    // Handle max_clients clients maximum at a time, so other clients will have to wait,
                     // their connection will be terminated
-                    if channels.size == MAX_CLIENTS {
+                    if channels.size == settings.max_clients {
                         break
                     }
                 },
@@ -171,13 +223,17 @@ fn game_(rx: Receiver<TcpStream>) {
             }
 
             let mut should_break = false;
-            for receiver in channels.receivers.iter() {
+            for (id, receiver) in channels.receivers.iter().enumerate() {
                 match receiver.try_recv() {
                     Ok(message) => match message {
                         ClientMessage::StartGame => {
                             should_break = true;
                             break;
                         },
+                        ClientMessage::JoinTeam(team) => {
+                            log(&format!("Client {} asks to join team {}", id, team));
+                            teams[id] = Some(team);
+                        },
                         // If message isn't a Start message, make thread panic
                         _ => panic!("Received wrong event"),
                     }
@@ -194,7 +250,22 @@ fn game_(rx: Receiver<TcpStream>) {
         }
 
         log("Creating game");
-        let mut game = Game::new(channels.size);
+        let mut game = match Game::new(channels.size, &settings) {
+            Ok(game) => game,
+            Err(e) => {
+                log(&format!("Could not create game: {}", e));
+                continue;
+            },
+        };
+        log(&format!("Game seed: {}", game.seed));
+        if let Some(e) = &game.map_error {
+            log(&format!("Could not generate a map from the game seed: {}", e));
+        }
+        if let Some(map_seed) = game.map_seed {
+            log(&format!("Map seed: {}", map_seed));
+        }
+        game.assign_teams(&teams);
+        log(&format!("Teams: {:?}", game.teams));
         
         // Make clients exit lobby
         log("Exiting lobby");
@@ -219,17 +290,28 @@ fn game_(rx: Receiver<TcpStream>) {
             log("Starting new turn");
             send_all(ClientEvent::SendNewTurn, &mut channels, &mut game);
 
-            // Wait client directions
+            // Wait directions from clients still playing, others are spectators
             log("Waiting client directions");
-            send_all(ClientEvent::WaitDirection, &mut channels, &mut game);
+            send_to(&game.alive_ids(), ClientEvent::WaitDirection, &mut channels, &mut game);
 
             // Once it's done receive directions in game thread
-            let directions = receive_all(&mut channels, &mut game);
+            let directions = receive_from(&game.alive_ids(), &mut channels, &mut game);
             log(&format!("Directions received: {:?}", directions));
-            let mut id = 0;
-            for snake in game.snakes.iter_mut() {
-                snake.direction = directions[id].clone();
-                id += 1;
+            let mut errors: Vec<(usize, String)> = vec![];
+            for (id, snake) in game.snakes.iter_mut().enumerate() {
+                let direction = match &directions[id] {
+                    Some(direction) => direction.clone(),
+                    None => continue,
+                };
+                // Reversals and unavailable directions are ignored, the snake keeps its current direction
+                if let Err(e) = snake.set_direction(direction, &game.settings.directions) {
+                    log(&format!("Client {}: {}", id, e));
+                    errors.push((id, e));
+                }
+            }
+            // Send errors from the highest id, so that removing a player doesn't shift the others
+            for (id, e) in errors.into_iter().rev() {
+                send_to(&[id], ClientEvent::SendError(e), &mut channels, &mut game);
             }
 
             // Play turn
@@ -240,17 +322,41 @@ fn game_(rx: Receiver<TcpStream>) {
             let turn_result = TurnData {
                 food: game.food.clone(),
                 snakes: game.snakes_to_vec(),
+                legal_directions: game.legal_directions(),
+                stats: game.stats.clone(),
+                zone: game.board.zone(game.board.shrink),
+                next_zone: game.upcoming_zone(),
+                effects: game.effects.clone(),
+                speeds: game.speeds(),
+                interval: game.schedule.interval,
+                hunger: game.hunger(),
+                teams: game.teams.clone(),
+                obstacles: game.obstacles.clone(),
+                objective: game.objective(),
             };
             log("Sending turn results");
             send_all(ClientEvent::SendTurnResult(turn_result), &mut channels, &mut game);
+            if !game.events.is_empty() {
+                log(&format!("Sending turn events: {:?}", game.events));
+                send_all(ClientEvent::SendEvents(game.events.clone()), &mut channels, &mut game);
+            }
             
             // Send GameState at the end of the turn
             let state = StateData { states: game.states.clone() };
             log("Sending current game state");
             send_all(ClientEvent::SendClientGameState(state), &mut channels, &mut game);
 
+            // Announce winners once the game mode says it is over
+            if let Some(winners) = game.winners() {
+                log(&format!("Game over, winners: {:?}", winners));
+                let standings = game.standings();
+                log(&format!("Final standings: {:?}", standings));
+                send_all(ClientEvent::SendGameOver(winners, standings), &mut channels, &mut game);
+                break;
+            }
+
             // Wait a bit, depending on game speed
-            thread::sleep(Duration::from_millis(SPEED as u64));
+            thread::sleep(Duration::from_millis(game.schedule.interval as u64));
         }
 
         log("Game is over, starting a new one");
@@ -294,7 +400,10 @@ fn handle_client(
         match receive::<ForceStartMessage>(&mut stream, &mut response) {
             Err(()) => (), // Handle this case more properly, we skip it for now
             Ok(message) => {
-                if message.force_start == true {
+                if let Some(team) = message.team {
+                    tx.send(ClientMessage::JoinTeam(team)).unwrap();
+                }
+                if message.force_start {
                     println!("test");
                     tx.send(ClientMessage::StartGame).unwrap();
                 }
@@ -312,8 +421,19 @@ fn handle_client(
                 id: ev.id,
                 width: config.width,
                 height: config.height,
+                mode: config.mode,
+                topology: config.topology,
+                map: config.map,
                 snakes: config.snakes,
                 food: config.food,
+                speed: config.speed,
+                max_clients: config.max_clients,
+                seed: config.seed,
+                map_seed: config.map_seed,
+                teams: config.teams,
+                directions: config.directions,
+                diagonal_crossing: config.diagonal_crossing,
+                legal_directions: config.legal_directions[ev.id].clone(),
             };
             send(&mut stream, config_message);
         },
@@ -332,7 +452,7 @@ fn handle_client(
                         tx.send(ClientMessage::Direction(dm.direction)).unwrap();
                     },
                     Err(()) => {
-                        log(&format!("Client closed connection, closing thread now"));
+                        log("Client closed connection, closing thread now");
                         break;
                     },
                 }
@@ -342,9 +462,33 @@ fn handle_client(
                     id: event.id,
                     food: turn_data.food,
                     snakes: turn_data.snakes,
+                    legal_directions: turn_data.legal_directions[event.id].clone(),
+                    stats: turn_data.stats,
+                    zone: turn_data.zone,
+                    next_zone: turn_data.next_zone,
+                    effects: turn_data.effects,
+                    speeds: turn_data.speeds,
+                    interval: turn_data.interval,
+                    hunger: turn_data.hunger,
+                    teams: turn_data.teams,
+                    obstacles: turn_data.obstacles,
+                    objective: turn_data.objective,
                 };
                 send(&mut stream, turn_message);
             },
+            ClientEvent::SendEvents(events) => {
+                for event in events {
+                    send(&mut stream, EventMessage { event });
+                }
+            },
+            ClientEvent::SendError(error) => {
+                send(&mut stream, ErrorMessage { error });
+            },
+            ClientEvent::SendGameOver(winners, standings) => {
+                send(&mut stream, EventMessage { event: game::GameEvent::GameOver { winners } });
+                send(&mut stream, StandingsMessage { standings });
+                break;
+            },
             ClientEvent::SendClientGameState(state_data) => {
                 send(&mut stream, StateMessage { state: state_data.states[event.id].clone() });
             },
@@ -358,6 +502,11 @@ fn main()
     // Reset log file
     File::create(LOG_FILE).unwrap();
 
+    // Load game settings from config file and command line flags
+    let args: Vec<String> = env::args().skip(1).collect();
+    let settings = GameSettings::from_args(&args).unwrap_or_else(|e| panic!("Invalid game settings: {}", e));
+    log(&format!("Game settings: {:?}", settings));
+
     // Create the complete address
     let addrs = format!("{}:{}", connection::SERVER_ADDR, connection::SERVER_PORT);
     println!("Starting server: server address = {}", addrs);
@@ -368,7 +517,7 @@ fn main()
 
     // Game thread
     let (tx, rx) = channel();
-    thread::spawn(move|| { game_(rx) });
+    thread::spawn(move|| { game_(rx, settings) });
 
     // Deal with incoming client connections
     for tcp_stream in listener.incoming() {
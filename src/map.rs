@@ -0,0 +1,256 @@
+use crate::game::*;
+use crate::snake::*;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+/// A fixed spawn point, the snake's head starts here heading to direction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Spawn {
+    pub position: Point,
+    pub direction: Direction,
+}
+
+/// Two linked cells, a snake entering one of them comes out of the other
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Portal {
+    pub a: Point,
+    pub b: Point,
+}
+
+/// Map layout
+/// Coordinates follow the board ones, the border is on x = 1, x = width, y = 1 and y = height
+///
+/// Maps are written either in JSON, with the fields of this structure, or as text,
+/// one character per cell:
+/// - `#` is a wall
+/// - `.` or a space is an empty cell
+/// - `*` is a cell where food can spawn
+/// - `^`, `v`, `<` and `>` are spawn points, giving the direction of the snake
+/// - `0` to `9` are portals, each digit marks both ends of one portal
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Map {
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub walls: Vec<Point>,
+    #[serde(default)]
+    pub spawns: Vec<Spawn>, // Snakes are placed automatically when empty
+    #[serde(default)]
+    pub food_zones: Vec<Point>, // Food spawns anywhere when empty
+    #[serde(default)]
+    pub portals: Vec<Portal>,
+}
+impl Map {
+    /// Create an empty map
+    pub fn empty(width: usize, height: usize) -> Self {
+        Map { width, height, ..Map::default() }
+    }
+
+    /// Load a map from a JSON or text file, depending on its extension
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read map file {}: {}", path, e))?;
+        let map = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid JSON map file {}: {}", path, e))?,
+            _ => Map::parse(&content)?,
+        };
+        map.validate()?;
+        return Ok(map);
+    }
+
+    /// Parse a map from its text representation
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut map = Map::empty(
+            lines.iter().map(|l| l.chars().count()).max().unwrap_or(0),
+            lines.len(),
+        );
+        let mut portals: BTreeMap<char, Vec<Point>> = BTreeMap::new();
+        for (row, line) in lines.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                let position = Point { x: col as u16 + 1, y: row as u16 + 1 };
+                let direction = match c {
+                    '#' => { map.walls.push(position); continue; },
+                    '*' => { map.food_zones.push(position); continue; },
+                    '.' | ' ' => continue,
+                    '0'..='9' => { portals.entry(c).or_default().push(position); continue; },
+                    '^' => Direction::Up,
+                    'v' => Direction::Down,
+                    '<' => Direction::Left,
+                    '>' => Direction::Right,
+                    _ => return Err(format!("Unknown map cell '{}' at line {}", c, row + 1)),
+                };
+                map.spawns.push(Spawn { position, direction });
+            }
+        }
+        for (c, mut ends) in portals {
+            if ends.len() != 2 {
+                return Err(format!("Portal '{}' must appear exactly twice (got {})", c, ends.len()));
+            }
+            let b = ends.pop().unwrap();
+            let a = ends.pop().unwrap();
+            map.portals.push(Portal { a, b });
+        }
+        return Ok(map);
+    }
+
+    /// Check if a point is a wall
+    pub fn is_wall(&self, p: &Point) -> bool {
+        return self.walls.contains(p);
+    }
+
+    /// Other end of the portal on a point, if there is one
+    pub fn portal_exit(&self, p: &Point) -> Option<&Point> {
+        return self.portals.iter().find_map(|portal| match p {
+            p if *p == portal.a => Some(&portal.b),
+            p if *p == portal.b => Some(&portal.a),
+            _ => None,
+        });
+    }
+
+    /// Check if a point is inside the border
+    fn is_inside(&self, p: &Point) -> bool {
+        return p.x > 1 && (p.x as usize) < self.width && p.y > 1 && (p.y as usize) < self.height;
+    }
+
+    /// Check that the map is usable
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width < 8 || self.height < 8 {
+            return Err(format!("Map must be at least 8x8 (got {}x{})", self.width, self.height));
+        }
+        for spawn in self.spawns.iter() {
+            if !self.is_inside(&spawn.position) || self.is_wall(&spawn.position) {
+                return Err(format!("Spawn point {:?} is not on a free cell", spawn.position));
+            }
+        }
+        for p in self.food_zones.iter() {
+            if !self.is_inside(p) {
+                return Err(format!("Food zone {:?} is outside the map", p));
+            }
+            if self.portal_exit(p).is_some() {
+                return Err(format!("Food zone {:?} is on a portal", p));
+            }
+        }
+        let mut ends: HashSet<&Point> = HashSet::new();
+        for p in self.portals.iter().flat_map(|portal| [&portal.a, &portal.b]) {
+            if !self.is_inside(p) || self.is_wall(p) || self.spawns.iter().any(|s| s.position == *p) {
+                return Err(format!("Portal {:?} is not on a free cell", p));
+            }
+            if !ends.insert(p) {
+                return Err(format!("Cell {:?} is used by several portals", p));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Cells reachable from a point without crossing walls or the border
+    pub fn reachable(&self, from: &Point) -> HashSet<Point> {
+        let mut seen: HashSet<Point> = HashSet::new();
+        let mut queue: VecDeque<Point> = VecDeque::new();
+        if self.is_inside(from) && !self.is_wall(from) {
+            seen.insert(from.clone());
+            queue.push_back(from.clone());
+        }
+        let walls: HashSet<&Point> = self.walls.iter().collect();
+        while let Some(p) = queue.pop_front() {
+            let neighbours = [(p.x - 1, p.y), (p.x + 1, p.y), (p.x, p.y - 1), (p.x, p.y + 1)];
+            for (x, y) in neighbours {
+                // Entering a portal leads to its other end
+                let next = Point { x, y };
+                let next = self.portal_exit(&next).cloned().unwrap_or(next);
+                if self.is_inside(&next) && !walls.contains(&next) && seen.insert(next.clone()) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        return seen;
+    }
+
+    /// Check that spawn points can reach each other, with at least `min_free` cells to move around
+    pub fn check_connected(&self, min_free: usize) -> Result<(), String> {
+        let first = match self.spawns.first() {
+            Some(spawn) => &spawn.position,
+            None => return Ok(()),
+        };
+        let reachable = self.reachable(first);
+        if let Some(spawn) = self.spawns.iter().find(|s| !reachable.contains(&s.position)) {
+            return Err(format!("Spawn point {:?} can't be reached from {:?}", spawn.position, first));
+        }
+        if reachable.len() < min_free {
+            return Err(format!("Only {} free cells can be reached, at least {} are needed", reachable.len(), min_free));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_text_map() {
+        let map = Map::parse("\
+##########
+#........#
+#.>....<.#
+#...##...#
+#...**...#
+#........#
+#.^......#
+##########").unwrap();
+        assert_eq!((map.width, map.height), (10, 8));
+        assert_eq!(map.walls.len(), 34);
+        assert_eq!(map.spawns, vec![
+            Spawn { position: Point { x: 3, y: 3 }, direction: Direction::Right },
+            Spawn { position: Point { x: 8, y: 3 }, direction: Direction::Left },
+            Spawn { position: Point { x: 3, y: 7 }, direction: Direction::Up },
+        ]);
+        assert_eq!(map.food_zones, vec![Point { x: 5, y: 5 }, Point { x: 6, y: 5 }]);
+        assert!(map.validate().is_ok());
+        assert!(Map::parse("#?#").is_err());
+    }
+
+    #[test]
+    fn portals() {
+        let mut map = Map::parse("\
+##########
+#>...#..1#
+#....#...#
+#1...#.<.#
+#....#...#
+#....#...#
+#....#...#
+##########").unwrap();
+        assert_eq!(map.portals, vec![Portal { a: Point { x: 9, y: 2 }, b: Point { x: 2, y: 4 } }]);
+        assert!(map.validate().is_ok());
+        assert!(map.check_connected(0).is_ok());
+        assert_eq!(map.portal_exit(&Point { x: 2, y: 4 }), Some(&Point { x: 9, y: 2 }));
+        assert!(Map::parse("#1.2.1#").is_err());
+
+        map.food_zones.push(Point { x: 2, y: 4 });
+        assert!(map.validate().is_err());
+    }
+
+    #[test]
+    fn spawn_points_must_be_connected() {
+        let map = Map::parse("\
+##########
+#>...#...#
+#....#...#
+#....#.<.#
+##########").unwrap();
+        assert!(map.check_connected(0).is_err());
+        let map = Map::parse("\
+##########
+#>...#...#
+#........#
+#....#.<.#
+##########").unwrap();
+        assert_eq!(map.reachable(&Point { x: 2, y: 2 }).len(), 22);
+        assert!(map.check_connected(22).is_ok());
+        assert!(map.check_connected(23).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::game::*;
+use crate::food::*;
+use crate::board::*;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+
+/// Game modes, with their parameters
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ModeKind {
+    Classic, // Last snakes standing win
+    TimeLimited { turns: usize }, // Highest score after a number of turns wins
+    FirstToLength { length: usize }, // First snakes reaching a length win
+    Endless, // Practice, goes on until every player is out of the game
+    BattleRoyale { interval: usize }, // Classic, with the border closing in by one ring every `interval` turns
+    Cooperative { food: usize, turns: usize, obstacle_every: usize }, // All snakes must eat `food` items within `turns` turns
+}
+impl ModeKind {
+    /// Create the rules of this mode
+    pub fn create(&self) -> Box<dyn GameMode> {
+        match self {
+            ModeKind::Classic => Box::new(Classic),
+            ModeKind::TimeLimited { turns } => Box::new(TimeLimited { turns: *turns }),
+            ModeKind::FirstToLength { length } => Box::new(FirstToLength { length: *length }),
+            ModeKind::Endless => Box::new(Endless),
+            ModeKind::BattleRoyale { interval } => Box::new(BattleRoyale { interval: *interval }),
+            ModeKind::Cooperative { food, turns, obstacle_every } => Box::new(Cooperative {
+                food: *food,
+                turns: *turns,
+                obstacle_every: *obstacle_every,
+                eaten: 0,
+            }),
+        }
+    }
+}
+impl FromStr for ModeKind {
+    type Err = String;
+
+    /// Parse `classic`, `time-limited:<turns>`, `first-to-length:<length>`, `endless`,
+    /// `battle-royale:<interval>` or `cooperative:<food>:<turns>:<obstacle_every>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default();
+        let params: Vec<&str> = parts.collect();
+        let param = |i: usize| -> Result<usize, String> {
+            params.get(i).ok_or(format!("Missing parameter for mode {}", name))?
+                .parse::<usize>().map_err(|_| format!("Invalid parameter for mode {}", name))
+        };
+        match name.to_lowercase().as_str() {
+            "classic" => Ok(ModeKind::Classic),
+            "time-limited" => Ok(ModeKind::TimeLimited { turns: param(0)? }),
+            "first-to-length" => Ok(ModeKind::FirstToLength { length: param(0)? }),
+            "endless" => Ok(ModeKind::Endless),
+            "battle-royale" => Ok(ModeKind::BattleRoyale { interval: param(0)? }),
+            "cooperative" => Ok(ModeKind::Cooperative { food: param(0)?, turns: param(1)?, obstacle_every: param(2)? }),
+            _ => Err(format!("Unknown mode {}", s)),
+        }
+    }
+}
+
+/// Safe zone announced to clients before it applies
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ZoneWarning {
+    pub zone: Zone,
+    pub turns: usize, // Turns still played in the current zone
+}
+
+/// Progress towards a shared objective
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Objective {
+    pub progress: usize,
+    pub goal: usize,
+    pub turns_left: usize,
+}
+
+/// Rules of a game mode
+/// Hooks are called by `Game::play_turn`, the game is over once `winners` returns something
+pub trait GameMode {
+    /// Mode announced to clients
+    fn kind(&self) -> ModeKind;
+
+    /// Called at the beginning of each turn, before snakes move
+    fn on_turn_start(&mut self, _game: &mut Game) {}
+
+    /// Called when snake `id` collides, returns whether the collision is fatal.
+    /// A snake surviving a collision stays where it is for this turn
+    fn on_collision(&mut self, _game: &mut Game, _id: usize, _collision: &Collision) -> bool {
+        return true;
+    }
+
+    /// Called when snake `id` has eaten a food item
+    fn on_food_eaten(&mut self, _game: &mut Game, _id: usize, _food: &Food) {}
+
+    /// Winners, once the game is over
+    fn winners(&self, game: &Game) -> Option<Vec<usize>>;
+
+    /// Next safe zone, for modes where the border moves
+    fn upcoming_zone(&self, _game: &Game) -> Option<ZoneWarning> {
+        return None;
+    }
+
+    /// Shared objective, for cooperative modes
+    fn objective(&self, _game: &Game) -> Option<Objective> {
+        return None;
+    }
+}
+
+/// Classic mode
+/// A game with more teams than `survivors` ends once only `survivors` teams have players left,
+/// otherwise it goes on until every player is out of the game. All players of the last teams win
+pub struct Classic;
+impl GameMode for Classic {
+    fn kind(&self) -> ModeKind {
+        return ModeKind::Classic;
+    }
+
+    fn winners(&self, game: &Game) -> Option<Vec<usize>> {
+        let teams = game.teams_of(&game.in_game_ids());
+        let over = match game.starting_teams > game.settings.survivors {
+            true => teams.len() <= game.settings.survivors,
+            false => teams.is_empty(),
+        };
+        return if over { Some(game.members_of(&teams)) } else { None };
+    }
+}
+
+/// Time-limited mode
+/// The game ends after a number of turns, or when every player is out of the game.
+/// Teams with the highest score win, eliminated players included
+pub struct TimeLimited {
+    pub turns: usize,
+}
+impl GameMode for TimeLimited {
+    fn kind(&self) -> ModeKind {
+        return ModeKind::TimeLimited { turns: self.turns };
+    }
+
+    fn winners(&self, game: &Game) -> Option<Vec<usize>> {
+        if game.turn < self.turns && !game.in_game_ids().is_empty() {
+            return None;
+        }
+        let teams = game.teams_of(&(0..game.snakes.len()).collect::<Vec<usize>>());
+        let best = teams.iter().map(|&team| game.team_score(team)).max().unwrap_or(0);
+        let winners: Vec<usize> = teams.into_iter().filter(|&team| game.team_score(team) == best).collect();
+        return Some(game.members_of(&winners));
+    }
+}
+
+/// First to length mode
+/// Teams of snakes reaching a length win, the game ends without winner when every player is out of the game
+pub struct FirstToLength {
+    pub length: usize,
+}
+impl GameMode for FirstToLength {
+    fn kind(&self) -> ModeKind {
+        return ModeKind::FirstToLength { length: self.length };
+    }
+
+    fn winners(&self, game: &Game) -> Option<Vec<usize>> {
+        let reached: Vec<usize> = game.alive_ids().into_iter()
+            .filter(|&id| game.snakes[id].body.len() >= self.length)
+            .collect();
+        if !reached.is_empty() || game.in_game_ids().is_empty() {
+            return Some(game.members_of(&game.teams_of(&reached)));
+        }
+        return None;
+    }
+}
+
+/// Endless mode
+/// Practice mode, without winner, the game goes on until every player is out of the game
+pub struct Endless;
+impl GameMode for Endless {
+    fn kind(&self) -> ModeKind {
+        return ModeKind::Endless;
+    }
+
+    fn winners(&self, game: &Game) -> Option<Vec<usize>> {
+        return if game.in_game_ids().is_empty() { Some(vec![]) } else { None };
+    }
+}
+
+/// Battle royale mode
+/// Classic rules, on an arena shrinking by one ring every `interval` turns, down to `MIN_ZONE_SIZE`.
+/// Snakes and food caught outside the safe zone when it shrinks are removed
+pub struct BattleRoyale {
+    pub interval: usize,
+}
+impl BattleRoyale {
+    /// Turns played when the arena shrinks next
+    fn next_shrink(&self, game: &Game) -> usize {
+        return self.interval.max(game.turn.div_ceil(self.interval) * self.interval);
+    }
+}
+impl GameMode for BattleRoyale {
+    fn kind(&self) -> ModeKind {
+        return ModeKind::BattleRoyale { interval: self.interval };
+    }
+
+    fn on_turn_start(&mut self, game: &mut Game) {
+        if game.turn == self.next_shrink(game) && game.board.can_shrink() {
+            game.shrink_zone();
+        }
+    }
+
+    fn winners(&self, game: &Game) -> Option<Vec<usize>> {
+        return Classic.winners(game);
+    }
+
+    fn upcoming_zone(&self, game: &Game) -> Option<ZoneWarning> {
+        if !game.board.can_shrink() {
+            return None;
+        }
+        return Some(ZoneWarning {
+            zone: game.board.zone(game.board.shrink + 1),
+            turns: self.next_shrink(game) - game.turn,
+        });
+    }
+}
+
+/// Cooperative mode
+/// All snakes work together to eat `food` items before `turns` turns are played,
+/// while a wall appears every `obstacle_every` turns. Everyone wins when the goal is reached,
+/// nobody wins when time runs out or every player is out of the game
+pub struct Cooperative {
+    pub food: usize,
+    pub turns: usize,
+    pub obstacle_every: usize, // 0 for no obstacles
+    eaten: usize,
+}
+impl GameMode for Cooperative {
+    fn kind(&self) -> ModeKind {
+        return ModeKind::Cooperative { food: self.food, turns: self.turns, obstacle_every: self.obstacle_every };
+    }
+
+    fn on_turn_start(&mut self, game: &mut Game) {
+        if self.obstacle_every > 0 && game.turn > 0 && game.turn.is_multiple_of(self.obstacle_every) {
+            game.spawn_obstacle();
+        }
+    }
+
+    fn on_food_eaten(&mut self, _game: &mut Game, _id: usize, food: &Food) {
+        if food.kind.effect().is_none() && food.kind != FoodKind::Poison {
+            self.eaten += 1;
+        }
+    }
+
+    fn winners(&self, game: &Game) -> Option<Vec<usize>> {
+        if self.eaten >= self.food {
+            return Some((0..game.snakes.len()).collect());
+        }
+        if game.turn >= self.turns || game.in_game_ids().is_empty() {
+            return Some(vec![]);
+        }
+        return None;
+    }
+
+    fn objective(&self, game: &Game) -> Option<Objective> {
+        return Some(Objective {
+            progress: self.eaten,
+            goal: self.food,
+            turns_left: self.turns.saturating_sub(game.turn),
+        });
+    }
+}
@@ -0,0 +1,96 @@
+use crate::settings::*;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+
+/// When the game speeds up
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RampUp {
+    Never, // Constant pace
+    Turns { every: usize }, // Every `every` turns
+    Food, // Every time a snake eats food
+}
+impl FromStr for RampUp {
+    type Err = String;
+
+    /// Parse `never`, `turns:<every>` or `food`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().split_once(':') {
+            Some(("turns", every)) => every.parse::<usize>()
+                .map(|every| RampUp::Turns { every })
+                .map_err(|_| format!("Invalid ramp-up interval {}", every)),
+            None if s.eq_ignore_ascii_case("never") => Ok(RampUp::Never),
+            None if s.eq_ignore_ascii_case("food") => Ok(RampUp::Food),
+            _ => Err(format!("Unknown ramp-up {}", s)),
+        }
+    }
+}
+
+/// Time between two turns, going down from `speed` to `min_interval` as the game goes on
+#[derive(Debug, Clone)]
+pub struct TickSchedule {
+    pub interval: usize, // Current time between two turns, in milliseconds
+    min: usize,
+    step: usize,
+    ramp_up: RampUp,
+}
+impl TickSchedule {
+    /// Create the schedule of a game
+    pub fn new(settings: &GameSettings) -> Self {
+        TickSchedule {
+            interval: settings.speed,
+            min: settings.min_interval,
+            step: settings.ramp_up_step,
+            ramp_up: settings.ramp_up.clone(),
+        }
+    }
+
+    /// Shorten the interval by one step
+    fn speed_up(&mut self) {
+        self.interval = self.interval.saturating_sub(self.step).max(self.min);
+    }
+
+    /// Called after each turn with the number of turns played
+    pub fn on_turn(&mut self, turn: usize) {
+        if let RampUp::Turns { every } = self.ramp_up {
+            if turn.is_multiple_of(every) {
+                self.speed_up();
+            }
+        }
+    }
+
+    /// Called when a snake eats food
+    pub fn on_food(&mut self) {
+        if self.ramp_up == RampUp::Food {
+            self.speed_up();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramp_up() {
+        let settings = GameSettings {
+            speed: 500, min_interval: 300, ramp_up_step: 150, ramp_up: RampUp::Turns { every: 2 },
+            ..GameSettings::default()
+        };
+        let mut schedule = TickSchedule::new(&settings);
+        let intervals: Vec<usize> = (1..=6).map(|turn| {
+            schedule.on_turn(turn);
+            schedule.on_food();
+            schedule.interval
+        }).collect();
+        assert_eq!(intervals, vec![500, 350, 350, 300, 300, 300]);
+
+        let mut schedule = TickSchedule::new(&GameSettings { ramp_up: RampUp::Food, ..settings });
+        schedule.on_turn(2);
+        assert_eq!(schedule.interval, 500);
+        schedule.on_food();
+        assert_eq!(schedule.interval, 350);
+
+        assert_eq!("turns:10".parse(), Ok(RampUp::Turns { every: 10 }));
+        assert!("turns:x".parse::<RampUp>().is_err());
+    }
+}
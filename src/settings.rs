@@ -0,0 +1,265 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use crate::food::*;
+use crate::board::*;
+use crate::map::*;
+use crate::spawn::*;
+use crate::mode::*;
+use crate::schedule::*;
+use crate::generator::*;
+use crate::snake::*;
+
+/// Game settings
+/// Loaded once at startup from a config file and/or command line flags
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameSettings {
+    pub width: usize,
+    pub height: usize,
+    pub map: Option<String>, // Map file, width and height are taken from it when set
+    pub generator: Option<MapStyle>, // Style of the map generated for each game, from the game seed, or from seed 0 if it fails
+    pub speed: usize, // Time between two turns at the beginning of the game, in milliseconds
+    pub min_interval: usize, // Shortest time between two turns, in milliseconds
+    pub ramp_up: RampUp, // When the time between two turns gets shorter
+    pub ramp_up_step: usize, // Milliseconds taken off the time between two turns when speeding up
+    pub max_clients: usize, // Max number of clients in a game
+    pub mode: ModeKind, // Rules deciding when the game ends and who wins
+    pub topology: Topology, // Whether snakes die on the border or wrap around
+    pub directions: DirectionSet, // Directions snakes can take
+    pub diagonal_crossing: bool, // Diagonal moves can pass between two consecutive cells of a snake, or cross another diagonal move
+    pub spawn_layout: SpawnLayout, // How snakes are placed when the map has no spawn points
+    pub spawn_clearance: usize, // Free cells around snakes when they are placed
+    pub seed: Option<u64>, // RNG seed, chosen by the server for each game if not set
+    pub length_tiebreak: bool, // In head-on collisions, the strictly longest snake survives
+    pub keep_corpses: bool, // Eliminated snakes stay on the board as obstacles
+    pub tail_cutting: bool, // A head hitting another snake's body cuts it instead of dying
+    pub hunger: usize, // Snakes lose a segment every this many turns without eating, 0 to disable
+    pub corpse_food: bool, // Eliminated snakes turn into food, one item per segment
+    pub corpse_decay: usize, // Turns before food left by corpses disappears
+    pub slow_down_every: usize, // Snakes lose a quarter cell per tick of speed every this many segments grown, 0 to disable
+    pub teams: usize, // Number of teams, 0 for a free-for-all
+    pub friendly_fire: bool, // Whether teammates collide with each other
+    pub lives: usize, // Eliminated snakes respawn until they have died this many times, 0 for unlimited lives
+    pub respawn_delay: usize, // Turns before an eliminated snake respawns
+    pub survivors: usize, // In classic mode, the game ends when this number of teams, or players, are left
+    pub max_food: usize, // Max number of food items on the field
+    pub food_spawn_chance: f64, // Chance to spawn a food item each turn, while under max_food
+    pub food_weights: FoodWeights, // Weights used to pick the kind of new food items
+}
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            width: 20,
+            height: 20,
+            map: None,
+            generator: None,
+            speed: 1000,
+            min_interval: 100,
+            ramp_up: RampUp::Never,
+            ramp_up_step: 50,
+            max_clients: 4,
+            mode: ModeKind::Classic,
+            topology: Topology::Bounded,
+            directions: DirectionSet::Orthogonal,
+            diagonal_crossing: false,
+            spawn_layout: SpawnLayout::Spread,
+            spawn_clearance: 2,
+            seed: None,
+            length_tiebreak: false,
+            keep_corpses: false,
+            tail_cutting: false,
+            hunger: 0,
+            corpse_food: false,
+            corpse_decay: 20,
+            slow_down_every: 0,
+            teams: 0,
+            friendly_fire: true,
+            lives: 1,
+            respawn_delay: 3,
+            survivors: 1,
+            max_food: 1,
+            food_spawn_chance: 1.0,
+            food_weights: FoodWeights::default(),
+        }
+    }
+}
+impl GameSettings {
+    /// Load settings from a TOML or JSON file, depending on its extension
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path, e))?;
+        let settings: GameSettings = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid JSON config file {}: {}", path, e))?,
+            _ => toml::from_str(&content)
+                .map_err(|e| format!("Invalid TOML config file {}: {}", path, e))?,
+        };
+        return Ok(settings);
+    }
+
+    /// Build settings from command line arguments
+    /// `--config <file>` is loaded first, then other flags override its values
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut settings = match args.iter().position(|a| a == "--config") {
+            Some(i) => match args.get(i + 1) {
+                Some(path) => GameSettings::from_file(path)?,
+                None => return Err(String::from("Missing value for --config")),
+            },
+            None => GameSettings::default(),
+        };
+
+        let mut i = 0;
+        while i < args.len() {
+            let flag = &args[i];
+            let value = args.get(i + 1).ok_or(format!("Missing value for {}", flag))?;
+            match flag.as_str() {
+                "--config" => (),
+                "--width" => settings.width = parse_value(flag, value)?,
+                "--height" => settings.height = parse_value(flag, value)?,
+                "--map" => settings.map = Some(value.clone()),
+                "--generate" => settings.generator = Some(parse_value(flag, value)?),
+                "--speed" => settings.speed = parse_value(flag, value)?,
+                "--min-interval" => settings.min_interval = parse_value(flag, value)?,
+                "--ramp-up" => settings.ramp_up = parse_value(flag, value)?,
+                "--ramp-up-step" => settings.ramp_up_step = parse_value(flag, value)?,
+                "--max-clients" => settings.max_clients = parse_value(flag, value)?,
+                "--mode" => settings.mode = parse_value(flag, value)?,
+                "--topology" => settings.topology = parse_value(flag, value)?,
+                "--directions" => settings.directions = parse_value(flag, value)?,
+                "--diagonal-crossing" => settings.diagonal_crossing = parse_value(flag, value)?,
+                "--spawn-layout" => settings.spawn_layout = parse_value(flag, value)?,
+                "--spawn-clearance" => settings.spawn_clearance = parse_value(flag, value)?,
+                "--seed" => settings.seed = Some(parse_value(flag, value)?),
+                "--length-tiebreak" => settings.length_tiebreak = parse_value(flag, value)?,
+                "--keep-corpses" => settings.keep_corpses = parse_value(flag, value)?,
+                "--tail-cutting" => settings.tail_cutting = parse_value(flag, value)?,
+                "--hunger" => settings.hunger = parse_value(flag, value)?,
+                "--corpse-food" => settings.corpse_food = parse_value(flag, value)?,
+                "--corpse-decay" => settings.corpse_decay = parse_value(flag, value)?,
+                "--slow-down-every" => settings.slow_down_every = parse_value(flag, value)?,
+                "--teams" => settings.teams = parse_value(flag, value)?,
+                "--friendly-fire" => settings.friendly_fire = parse_value(flag, value)?,
+                "--lives" => settings.lives = parse_value(flag, value)?,
+                "--respawn-delay" => settings.respawn_delay = parse_value(flag, value)?,
+                "--survivors" => settings.survivors = parse_value(flag, value)?,
+                "--max-food" => settings.max_food = parse_value(flag, value)?,
+                "--food-spawn-chance" => settings.food_spawn_chance = parse_value(flag, value)?,
+                _ => return Err(format!("Unknown flag {}", flag)),
+            }
+            i += 2;
+        }
+
+        settings.validate()?;
+        return Ok(settings);
+    }
+
+    /// Check that a game can be played with these settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_clients == 0 {
+            return Err(String::from("max_clients must be at least 1"));
+        }
+        if self.map.is_some() && self.generator.is_some() {
+            return Err(String::from("map and generator can't be both set"));
+        }
+        let map = self.load_map(self.seed.unwrap_or(0))?;
+        map.validate()?;
+        if map.spawns.is_empty() {
+            // Snakes must fit on the board when the game is full
+            let board = Board::from_map(&map, self.topology.clone());
+            SpawnPlanner::plan(&board, self.max_clients, self.spawn_clearance, &self.spawn_layout)?;
+        } else if map.spawns.len() < self.max_clients {
+            return Err(format!("Map has {} spawn points for {} clients", map.spawns.len(), self.max_clients));
+        } else {
+            // Bodies lie behind spawn points, they must be on free cells too
+            let board = Board::from_map(&map, self.topology.clone());
+            for spawn in map.spawns.iter() {
+                let body = Snake::spawn(spawn.position.clone(), spawn.direction.clone(), &board).body;
+                if body.iter().any(|p| board.is_border(p) || board.is_wall(p) || board.is_portal(p)) {
+                    return Err(format!("Snake spawning on {:?} doesn't fit on the map", spawn.position));
+                }
+            }
+        }
+        match self.mode {
+            ModeKind::TimeLimited { turns: 0 } => return Err(String::from("Time-limited mode needs at least 1 turn")),
+            ModeKind::FirstToLength { length: 0 } => return Err(String::from("First to length mode needs a length of at least 1")),
+            ModeKind::BattleRoyale { interval: 0 } => return Err(String::from("Battle royale mode needs an interval of at least 1 turn")),
+            ModeKind::Cooperative { food: 0, .. } | ModeKind::Cooperative { turns: 0, .. } => {
+                return Err(String::from("Cooperative mode needs a goal and a time limit of at least 1"));
+            },
+            ModeKind::BattleRoyale { .. } if self.topology == Topology::Wrapping => {
+                return Err(String::from("Battle royale mode needs a bounded board"));
+            },
+            _ => (),
+        }
+        if self.teams == 1 || self.teams > self.max_clients {
+            return Err(format!("teams must be 0, or between 2 and max_clients (got {})", self.teams));
+        }
+        if self.lives == 0 && !matches!(self.mode, ModeKind::TimeLimited { .. }) {
+            return Err(String::from("Unlimited lives are only available in time-limited mode"));
+        }
+        if self.respawn_delay == 0 {
+            return Err(String::from("respawn_delay must be at least 1 turn"));
+        }
+        if self.min_interval > self.speed {
+            return Err(format!("min_interval ({}) must not be above speed ({})", self.min_interval, self.speed));
+        }
+        if self.ramp_up == (RampUp::Turns { every: 0 }) {
+            return Err(String::from("Ramp-up interval must be at least 1 turn"));
+        }
+        if self.corpse_food && self.keep_corpses {
+            return Err(String::from("corpse_food and keep_corpses can't be both set"));
+        }
+        if self.max_food == 0 {
+            return Err(String::from("max_food must be at least 1"));
+        }
+        if !(0.0..=1.0).contains(&self.food_spawn_chance) {
+            return Err(format!("food_spawn_chance must be between 0 and 1 (got {})", self.food_spawn_chance));
+        }
+        if self.food_weights.total() == 0 {
+            return Err(String::from("At least one food weight must be positive"));
+        }
+        return Ok(());
+    }
+
+    /// Load the map file, generate a map from the seed, or create an empty map of the given size
+    pub fn load_map(&self, seed: u64) -> Result<Map, String> {
+        match (&self.map, &self.generator) {
+            (Some(path), _) => Map::load(path),
+            (None, Some(style)) => MapGenerator::generate(style, self, seed),
+            (None, None) => Ok(Map::empty(self.width, self.height)),
+        }
+    }
+}
+
+/// Parse the value of a command line flag
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    return value.parse::<T>().map_err(|_| format!("Invalid value for {}: {}", flag, value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        return s.split_whitespace().map(String::from).collect();
+    }
+
+    #[test]
+    fn command_line() {
+        let path = std::env::temp_dir().join("snake_settings.toml");
+        fs::write(&path, "width = 30\nheight = 25\nmax_food = 3\n").unwrap();
+        let config = path.to_string_lossy();
+
+        // Flags override the config file, whatever their position
+        let settings = GameSettings::from_args(&args(&format!("--width 40 --config {} --max-food 2", config))).unwrap();
+        assert_eq!((settings.width, settings.height, settings.max_food), (40, 25, 2));
+
+        assert_eq!(GameSettings::from_args(&args("--colour red")).unwrap_err(), "Unknown flag --colour");
+        assert_eq!(GameSettings::from_args(&args("--width 30 --height")).unwrap_err(), "Missing value for --height");
+        assert!(GameSettings::from_args(&args("--width wide")).is_err());
+        assert!(GameSettings { teams: 1, ..GameSettings::default() }.validate().is_err());
+        assert!(GameSettings::from_args(&args("--corpse-food true --keep-corpses true")).is_err());
+    }
+}
@@ -1,87 +1,196 @@
 use crate::game::*;
-use serde::{Deserialize};
+use crate::board::*;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+
+// Length of a snake at the beginning of a game
+pub const INITIAL_LENGTH: usize = 3;
+// Length under which a snake can't shrink
+pub const MIN_LENGTH: usize = 2;
+// Speed of a snake moving one cell per tick, speeds are in quarters of cell per tick
+pub const NORMAL_SPEED: usize = 4;
+// Slowest speed, one cell every four ticks
+pub const MIN_SPEED: usize = 1;
+// Steps in a tick, snakes at max speed move on every step
+pub const STEPS: usize = 2;
+// Fastest speed, one cell per step
+pub const MAX_SPEED: usize = STEPS * NORMAL_SPEED;
 
 /// Directions
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+impl Direction {
+    /// Orthogonal directions
+    pub fn orthogonal() -> Vec<Direction> {
+        return vec![Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+    }
+
+    /// Diagonal directions
+    pub fn diagonals() -> Vec<Direction> {
+        return vec![Direction::UpLeft, Direction::UpRight, Direction::DownLeft, Direction::DownRight];
+    }
+
+    /// Direction going the other way
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+
+    /// Vertical and horizontal parts of a diagonal direction
+    pub fn components(&self) -> Option<(Direction, Direction)> {
+        match self {
+            Direction::UpLeft => Some((Direction::Up, Direction::Left)),
+            Direction::UpRight => Some((Direction::Up, Direction::Right)),
+            Direction::DownLeft => Some((Direction::Down, Direction::Left)),
+            Direction::DownRight => Some((Direction::Down, Direction::Right)),
+            _ => None,
+        }
+    }
+}
+
+/// Directions snakes can take
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DirectionSet {
+    Orthogonal, // Up, down, left and right
+    Diagonal, // Orthogonal and diagonal directions
+}
+impl DirectionSet {
+    /// Directions in the set
+    pub fn directions(&self) -> Vec<Direction> {
+        match self {
+            DirectionSet::Orthogonal => Direction::orthogonal(),
+            DirectionSet::Diagonal => [Direction::orthogonal(), Direction::diagonals()].concat(),
+        }
+    }
+}
+impl FromStr for DirectionSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "orthogonal" => Ok(DirectionSet::Orthogonal),
+            "diagonal" => Ok(DirectionSet::Diagonal),
+            _ => Err(format!("Unknown direction set {}", s)),
+        }
+    }
 }
 
 /// Snake's structure
+#[derive(Clone)]
 pub struct Snake {
     pub body: Vec<Point>, // Vec of points representing the body of the snake
     pub direction: Direction, // Current direction of our snake
+    pub heading: Direction, // Direction of the last move
 }
 impl Snake {
-    /// Init the snake at the center of the screen, moving in towards the right
-    pub fn init(id: usize, nb: usize, width: usize, height: usize) -> Self {
-        let mut body = vec![];
-        body.push(Point { x: (width as u16) / 2 - 1, y: (height / (2 * nb) * (id + 1)) as u16 });
-        body.push(Point { x: (width as u16) / 2, y: (height / (2 * nb) * (id + 1)) as u16 });
-        body.push(Point { x: (width as u16) / 2 + 1, y: (height / (2* nb) * (id +1)) as u16 });
-        Snake { body, direction: Direction::Right }
+    /// Create a snake from its body (tail first), heading to direction
+    pub fn new(body: Vec<Point>, direction: Direction) -> Self {
+        Snake { body, heading: direction.clone(), direction }
+    }
+
+    /// Init the snake with its head on a spawn point, its body lies behind it
+    pub fn spawn(head: Point, direction: Direction, board: &Board) -> Self {
+        let back = direction.opposite();
+        let mut body = vec![head];
+        for _ in 1..INITIAL_LENGTH {
+            let p = board.step(body.last().unwrap(), &back);
+            body.push(p);
+        }
+        body.reverse();
+        Snake::new(body, direction)
+    }
+
+    /// Directions of a set the snake can take next, every direction but back on its neck
+    pub fn legal_directions(&self, set: &DirectionSet) -> Vec<Direction> {
+        let reverse = self.heading.opposite();
+        return set.directions().into_iter().filter(|d| *d != reverse).collect();
+    }
+
+    /// Change the direction of the snake, reversals and directions out of the set are rejected
+    pub fn set_direction(&mut self, direction: Direction, set: &DirectionSet) -> Result<(), String> {
+        if !set.directions().contains(&direction) {
+            return Err(format!("Direction {:?} is not available in this game", direction));
+        }
+        if direction == self.heading.opposite() {
+            return Err(format!("Illegal direction {:?}, snake is heading {:?}", direction, self.heading));
+        }
+        self.direction = direction;
+        return Ok(());
+    }
+
+    /// Position of the head after the next move
+    pub fn next_head(&self, board: &Board) -> Point {
+        return board.step(self.body.last().unwrap(), &self.direction);
     }
 
     /// Move the snake for one play
-    pub fn _move(&mut self) {
-        self.body.remove(0); // Safe remove, our snake is always of sz >= 3
-        let p = self.body.last().unwrap();
-        let point: Point = match self.direction {
-            Direction::Up => Point {x: p.x, y: p.y - 1 },
-            Direction::Down => Point { x: p.x, y: p.y + 1 },
-            Direction::Right => Point { x: p.x + 1, y: p.y },
-            Direction::Left => Point { x: p.x - 1, y: p.y },
-        };
+    pub fn _move(&mut self, board: &Board) {
+        let point = self.next_head(board);
+        self.body.remove(0); // Safe remove, our snake is always of sz >= MIN_LENGTH
         self.body.push(point);
+        self.heading = self.direction.clone();
     }
 
     /// Make the snake grow
     pub fn _grow(&mut self, food: Point) {
         self.body.push(food);
+        self.heading = self.direction.clone();
     }
 
-    /// Check if snake body overlaps with point
-    pub fn _do_overlap(&self, point: Point) -> bool {
-        for p in self.body.iter() {
-            if (p.x == point.x) && (p.y == point.y) {
-                return true;
-            }
+    /// Make the snake shrink, losing its tail
+    pub fn _shrink(&mut self) {
+        if self.body.len() > MIN_LENGTH {
+            self.body.remove(0);
         }
-        return false;
     }
+}
 
-    /// Check collisions with border
-    pub fn _check_border_collisions(&self, width: usize, height: usize) -> bool {
-        let p = self.body.last().unwrap();
-        if (p.x == 1) || (p.x == (width as u16)) {
-            return true;
-        }
-        if (p.y == 1) || (p.y == (height as u16)) {
-            return true;
-        }
-        return false;
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    /// Check self collisions
-    pub fn _check_self_collision(&self) -> bool {
-        let last = self.body.last().unwrap();
-        for p in self.body.iter() {
-            if (p as *const _) != (last as *const _) && p.x == last.x && p.y == last.y {
-                return true;
-            }
-        }
-        return false;
+    #[test]
+    fn reversal_is_rejected() {
+        let board = Board::new(20, 20, Topology::Bounded);
+        let mut snake = Snake::spawn(Point { x: 11, y: 10 }, Direction::Right, &board);
+        let set = DirectionSet::Orthogonal;
+        assert_eq!(snake.legal_directions(&set), vec![Direction::Up, Direction::Down, Direction::Right]);
+        assert!(snake.set_direction(Direction::Left, &set).is_err());
+        assert_eq!(snake.direction, Direction::Right);
+
+        // Turning twice before moving can't be used to go back either
+        snake.set_direction(Direction::Up, &set).unwrap();
+        assert!(snake.set_direction(Direction::Left, &set).is_err());
+        snake._move(&board);
+        assert!(snake.set_direction(Direction::Left, &set).is_ok());
     }
 
-    /// Check collisions with food elements
-    pub fn _check_food_collision(&self, point: Point) -> bool {
-        let p = self.body.last().unwrap();
-        if (p.x == point.x) && (p.y == point.y) {
-            return true;
-        }
-        return false;
+    #[test]
+    fn diagonal_directions() {
+        let board = Board::new(20, 20, Topology::Bounded);
+        let mut snake = Snake::spawn(Point { x: 11, y: 10 }, Direction::Right, &board);
+        assert!(snake.set_direction(Direction::UpRight, &DirectionSet::Orthogonal).is_err());
+        assert_eq!(snake.legal_directions(&DirectionSet::Diagonal).len(), 7);
+        snake.set_direction(Direction::UpRight, &DirectionSet::Diagonal).unwrap();
+        snake._move(&board);
+        assert_eq!(snake.body, vec![Point { x: 10, y: 10 }, Point { x: 11, y: 10 }, Point { x: 12, y: 9 }]);
+        assert!(snake.set_direction(Direction::DownLeft, &DirectionSet::Diagonal).is_err());
     }
 }
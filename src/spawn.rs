@@ -0,0 +1,221 @@
+use crate::game::*;
+use crate::snake::*;
+use crate::board::*;
+use crate::map::*;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use std::cmp::Reverse;
+
+/// How snakes are placed at the beginning of a game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SpawnLayout {
+    Spread, // As far as possible from each other
+    Symmetric, // Face to face on evenly spaced rows, mirrored around the vertical axis
+}
+impl FromStr for SpawnLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spread" => Ok(SpawnLayout::Spread),
+            "symmetric" => Ok(SpawnLayout::Symmetric),
+            _ => Err(format!("Unknown spawn layout {}", s)),
+        }
+    }
+}
+
+/// Chebyshev distance between two points
+pub fn distance(a: &Point, b: &Point) -> usize {
+    let dx = (a.x as i32 - b.x as i32).unsigned_abs();
+    let dy = (a.y as i32 - b.y as i32).unsigned_abs();
+    return dx.max(dy) as usize;
+}
+
+/// Ranking of a spawn point when spreading snakes: distance to other snakes, then distance to the center
+type SpreadKey = (usize, Reverse<usize>);
+
+/// Spawn planner
+/// A snake can start somewhere if its body is inside the border, off walls and portals,
+/// the `clearance` cells in front of its head are free,
+/// and more than `clearance` cells separate it from other snakes
+pub struct SpawnPlanner<'a> {
+    board: &'a Board,
+    clearance: usize,
+    bodies: Vec<Vec<Point>>,
+    blocked: Vec<Point>, // Cells snakes can't start on, without clearance around them
+}
+impl<'a> SpawnPlanner<'a> {
+    /// Create new SpawnPlanner
+    pub fn new(board: &'a Board, clearance: usize) -> Self {
+        SpawnPlanner { board, clearance, bodies: vec![], blocked: vec![] }
+    }
+
+    /// Add a snake already on the board
+    pub fn add_body(&mut self, body: Vec<Point>) {
+        self.bodies.push(body);
+    }
+
+    /// Forbid a cell, such as a food item
+    pub fn block(&mut self, p: Point) {
+        self.blocked.push(p);
+    }
+
+    /// Find a starting position for one more snake
+    /// The first fitting candidate is taken, snakes are spread when there is no candidate
+    pub fn place(&mut self, candidates: &[Spawn]) -> Option<Spawn> {
+        if candidates.is_empty() {
+            return self.spread(1).ok().map(|mut spawns| spawns.remove(0));
+        }
+        return candidates.iter().find(|spawn| self.fit(spawn).is_some()).cloned();
+    }
+
+    /// Plan starting positions for nb snakes
+    pub fn plan(board: &Board, nb: usize, clearance: usize, layout: &SpawnLayout) -> Result<Vec<Spawn>, String> {
+        let mut planner = SpawnPlanner::new(board, clearance);
+        let spawns = match layout {
+            SpawnLayout::Spread => planner.spread(nb)?,
+            SpawnLayout::Symmetric => planner.symmetric(nb)?,
+        };
+        return Ok(spawns);
+    }
+
+    /// Check if a cell is inside the border, off walls and portals, and not blocked
+    fn is_open(&self, p: &Point) -> bool {
+        return !self.board.is_border(p) && !self.board.is_wall(p) && !self.board.is_portal(p) && !self.blocked.contains(p);
+    }
+
+    /// Body of a snake starting on a spawn point, if it fits with the snakes already placed
+    fn fit(&self, spawn: &Spawn) -> Option<Vec<Point>> {
+        let body = Snake::spawn(spawn.position.clone(), spawn.direction.clone(), self.board).body;
+        if !body.iter().all(|p| self.is_open(p)) {
+            return None;
+        }
+        let mut ahead = spawn.position.clone();
+        for _ in 0..self.clearance {
+            ahead = self.board.step(&ahead, &spawn.direction);
+            if !self.is_open(&ahead) || self.bodies.iter().flatten().any(|p| *p == ahead) {
+                return None;
+            }
+        }
+        let too_close = self.bodies.iter().flatten()
+            .any(|p| body.iter().any(|q| distance(p, q) <= self.clearance));
+        if too_close {
+            return None;
+        }
+        return Some(body);
+    }
+
+    /// Place snakes one by one, each one as far as possible from those already placed.
+    /// The first one is placed as far as possible from the center, ties go to snakes heading to the center
+    fn spread(&mut self, nb: usize) -> Result<Vec<Spawn>, String> {
+        let center = Point { x: (self.board.width as u16).div_ceil(2), y: (self.board.height as u16).div_ceil(2) };
+        let mut spawns: Vec<Spawn> = vec![];
+        for id in 0..nb {
+            let mut best: Option<(SpreadKey, Spawn, Vec<Point>)> = None;
+            for y in 2..self.board.height as u16 {
+                for x in 2..self.board.width as u16 {
+                    for direction in Direction::orthogonal() {
+                        let spawn = Spawn { position: Point { x, y }, direction };
+                        let body = match self.fit(&spawn) {
+                            Some(body) => body,
+                            None => continue,
+                        };
+                        let spread = match self.bodies.is_empty() {
+                            true => distance(&spawn.position, &center),
+                            false => self.bodies.iter().flatten()
+                                .map(|p| body.iter().map(|q| distance(p, q)).min().unwrap())
+                                .min().unwrap(),
+                        };
+                        let next = self.board.step(&spawn.position, &spawn.direction);
+                        let key = (spread, Reverse(distance(&next, &center)));
+                        if best.as_ref().is_none_or(|(k, _, _)| key > *k) {
+                            best = Some((key, spawn, body));
+                        }
+                    }
+                }
+            }
+            match best {
+                Some((_, spawn, body)) => {
+                    self.bodies.push(body);
+                    spawns.push(spawn);
+                },
+                None => return Err(format!(
+                    "No room for snake {} on a {}x{} board with a clearance of {}",
+                    id, self.board.width, self.board.height, self.clearance
+                )),
+            }
+        }
+        return Ok(spawns);
+    }
+
+    /// Place snakes by pairs on evenly spaced rows, facing each other from both sides of the board.
+    /// With an odd number of snakes, the last one is alone on its row, in the middle, heading up
+    fn symmetric(&mut self, nb: usize) -> Result<Vec<Spawn>, String> {
+        let rows = nb.div_ceil(2);
+        let (width, height) = (self.board.width as u16, self.board.height as u16);
+        let tail = 2 + self.clearance as u16;
+        let mut spawns: Vec<Spawn> = vec![];
+        for id in 0..nb {
+            let row = (id / 2) as u16;
+            let y = (row + 1) * (height + 1) / (rows as u16 + 1);
+            let head = (INITIAL_LENGTH - 1) as u16;
+            let spawn = match (id % 2, id == nb - 1) {
+                (0, true) => Spawn { position: Point { x: width.div_ceil(2), y }, direction: Direction::Up },
+                (0, false) => Spawn { position: Point { x: tail + head, y }, direction: Direction::Right },
+                _ => Spawn { position: Point { x: width + 1 - tail - head, y }, direction: Direction::Left },
+            };
+            match self.fit(&spawn) {
+                Some(body) => self.bodies.push(body),
+                None => return Err(format!(
+                    "Symmetric layout for {} snakes doesn't fit on a {}x{} board with a clearance of {}",
+                    nb, self.board.width, self.board.height, self.clearance
+                )),
+            }
+            spawns.push(spawn);
+        }
+        return Ok(spawns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_spawns() {
+        let cases = vec![
+            (1, 20, 20, SpawnLayout::Spread, true),
+            (4, 20, 20, SpawnLayout::Spread, true),
+            (12, 30, 30, SpawnLayout::Spread, true),
+            (6, 10, 10, SpawnLayout::Spread, false),
+            (4, 20, 20, SpawnLayout::Symmetric, true),
+            (5, 20, 20, SpawnLayout::Symmetric, true),
+            (8, 12, 12, SpawnLayout::Symmetric, false),
+        ];
+        for (nb, width, height, layout, fits) in cases {
+            let board = Board::new(width, height, Topology::Bounded);
+            let spawns = SpawnPlanner::plan(&board, nb, 2, &layout);
+            assert_eq!(spawns.is_ok(), fits, "{} snakes on {}x{} ({:?})", nb, width, height, layout);
+            if let Ok(spawns) = spawns {
+                assert_eq!(spawns.len(), nb);
+                // Placing them again one by one must succeed
+                let mut planner = SpawnPlanner::new(&board, 2);
+                for spawn in spawns.iter() {
+                    let body = planner.fit(spawn).unwrap();
+                    planner.bodies.push(body);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_layout_is_mirrored() {
+        let board = Board::new(21, 20, Topology::Bounded);
+        let spawns = SpawnPlanner::plan(&board, 4, 2, &SpawnLayout::Symmetric).unwrap();
+        for pair in spawns.chunks(2) {
+            assert_eq!(pair[0].position.y, pair[1].position.y);
+            assert_eq!(pair[0].position.x + pair[1].position.x, 22);
+            assert_eq!(pair[0].direction, pair[1].direction.opposite());
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Serialize};
+use std::cmp::Reverse;
+
+// Points given for each normal food eaten
+pub const FOOD_POINTS: usize = 1;
+// Points given for each bonus food eaten
+pub const BONUS_POINTS: usize = 3;
+// Points given for each snake killed
+pub const KILL_POINTS: usize = 5;
+// Points given for each segment cut off another snake
+pub const CUT_POINTS: usize = 1;
+
+/// Cause of a snake's death
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum DeathCause {
+    Border,
+    Wall,
+    SelfCollision,
+    Snake { killer: usize }, // Ran into the body of another snake
+    Corpse, // Ran into the corpse of an eliminated snake
+    HeadOn { killer: usize }, // Met the head of another snake
+    Poison,
+    Starvation, // Went hungry while at its minimum length
+}
+
+/// Statistics of one player
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PlayerStats {
+    pub score: usize,
+    pub length: usize,
+    pub food_eaten: usize,
+    pub kills: usize,
+    pub segments_cut: usize,
+    pub deaths: usize,
+    pub turns_survived: usize,
+    pub cause_of_death: Option<DeathCause>,
+}
+impl PlayerStats {
+    /// Create stats for a snake of given length
+    pub fn new(length: usize) -> Self {
+        PlayerStats { length, ..PlayerStats::default() }
+    }
+
+    /// Record a food eaten
+    pub fn eat(&mut self, points: usize) {
+        self.food_eaten += 1;
+        self.score += points;
+    }
+
+    /// Record a kill
+    pub fn kill(&mut self) {
+        self.kills += 1;
+        self.score += KILL_POINTS;
+    }
+
+    /// Record segments cut off another snake
+    pub fn cut(&mut self, segments: usize) {
+        self.segments_cut += segments;
+        self.score += CUT_POINTS * segments;
+    }
+}
+
+/// Rank of a player at the end of a game
+#[derive(Serialize, Debug, Clone)]
+pub struct Standing {
+    pub rank: usize,
+    pub id: usize,
+    pub stats: PlayerStats,
+}
+
+/// Rank players: survivors first, then players who survived longer, then higher scores
+/// Players with the same results share the same rank
+pub fn standings(stats: &[PlayerStats]) -> Vec<Standing> {
+    let key = |s: &PlayerStats| (s.cause_of_death.is_none(), s.turns_survived, s.score);
+    let mut ids: Vec<usize> = (0..stats.len()).collect();
+    ids.sort_by_key(|&id| Reverse(key(&stats[id])));
+
+    let mut standings: Vec<Standing> = vec![];
+    for (i, &id) in ids.iter().enumerate() {
+        let rank = match standings.last() {
+            Some(last) if key(&last.stats) == key(&stats[id]) => last.rank,
+            _ => i + 1,
+        };
+        standings.push(Standing { rank, id, stats: stats[id].clone() });
+    }
+    return standings;
+}